@@ -1,14 +1,18 @@
 const DEFAULT_PAYLOAD: &str = "'{\"path\": \"\", \"payload\": \"\"}'";
-//const TIMEOUT: u8 = 3;
-const TIMEOUT: u8 = 10;     // Adapting to poor network conditions.
-                            // ToDo: Users can adjust settings based on their network quality.
-#[cfg(target_feature = "deprecated")]
+#[cfg(feature = "github_api")]
 const GEO_URI: &str = "https://api.github.com/repos/MetaCubeX/meta-rules-dat/releases/latest";
-#[cfg(target_feature = "deprecated")]
+#[cfg(feature = "github_api")]
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+// A read of a stream gives up after this long, so the reader can tell whether to go on
+const STREAM_IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
 
-use std::io::Result;
+use crate::{ApiError, ClashConfig, ClashVersion, NetConfig, Result};
 use minreq::Method;
+use std::collections::HashMap;
+use crate::{
+    ClashConnections, ClashLog, DnsQuery, ClashProviders, ClashProxies, ClashRules, LogLevel, Memory,
+    ProxyDelay, Traffic,
+};
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum ProfileSectionType {
@@ -17,67 +21,214 @@ pub enum ProfileSectionType {
     RuleProvider,
 }
 
-trait ResProcess {
-    fn process(self) -> core::result::Result<String, minreq::Error>;
-}
-impl ResProcess for minreq::Response {
-    fn process(self) -> core::result::Result<String, minreq::Error> {
-        self.as_str().map(|s| s.to_owned())
+impl ProfileSectionType {
+    /// Path of the providers in the controller api
+    fn provider_path(&self) -> Result<&'static str> {
+        match self {
+            ProfileSectionType::ProxyProvider => Ok("/providers/proxies"),
+            ProfileSectionType::RuleProvider => Ok("/providers/rules"),
+            ProfileSectionType::Profile => Err(ApiError::InvalidInput("Not a provider")),
+        }
     }
 }
 
-fn process_err(e: minreq::Error) -> std::io::Error {
-    use std::io::{Error, ErrorKind};
-    match e {
-        minreq::Error::AddressNotFound | minreq::Error::PunycodeConversionFailed => {
-            Error::new(ErrorKind::AddrNotAvailable, e)
+/// The core answers after the test `timeout`(ms), give it some extra time
+fn delay_req_timeout(timeout: u64, min: u64) -> u64 {
+    (timeout / 1000 + 1).max(min)
+}
+
+/// Percent-encode a single path segment, names of proxies might contain anything
+fn encode_path(seg: &str) -> String {
+    let mut out = String::with_capacity(seg.len());
+    for b in seg.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
         }
-        minreq::Error::IoError(e) => e,
-        minreq::Error::HeadersOverflow
-        | minreq::Error::StatusLineOverflow
-        | minreq::Error::InvalidUtf8InBody(_)
-        | minreq::Error::InvalidUtf8InResponse
-        | minreq::Error::MalformedChunkLength
-        | minreq::Error::MalformedChunkEnd
-        | minreq::Error::MalformedContentLength => Error::new(ErrorKind::InvalidData, e),
-        minreq::Error::RedirectLocationMissing
-        | minreq::Error::InfiniteRedirectionLoop
-        | minreq::Error::TooManyRedirections => Error::new(ErrorKind::ConnectionAborted, e),
-        minreq::Error::HttpsFeatureNotEnabled => unreachable!("https should already be enabled"),
-        minreq::Error::PunycodeFeatureNotEnabled => panic!("{}", e),
-        minreq::Error::RustlsCreateConnection(_) => Error::new(ErrorKind::ConnectionRefused, e),
-        minreq::Error::BadProxy
-        | minreq::Error::BadProxyCreds
-        | minreq::Error::ProxyConnect
-        | minreq::Error::InvalidProxyCreds => Error::new(ErrorKind::PermissionDenied, e),
-        minreq::Error::Other(i) => Error::new(ErrorKind::Other, i),
     }
+    out
 }
 
-pub struct Resp(minreq::ResponseLazy);
+pub struct Resp(RespBody);
+enum RespBody {
+    Http(minreq::ResponseLazy),
+    // read by our own client, see [Transport]
+    Raw(Box<dyn std::io::BufRead + Send>),
+}
 impl Resp {
-    pub fn copy_to<W: ?Sized>(self, w: &mut W) -> std::io::Result<u64>
+    pub fn copy_to<W>(self, w: &mut W) -> std::io::Result<u64>
     where
-        W: std::io::Write,
+        W: std::io::Write + ?Sized,
     {
-        let Resp(mut inner) = self;
-        std::io::copy(&mut inner, w)
+        match self.0 {
+            RespBody::Http(mut inner) => std::io::copy(&mut inner, w),
+            RespBody::Raw(mut inner) => std::io::copy(&mut inner, w),
+        }
     }
+    /// A header of the response, `name` is lowercase
+    pub fn header(&self, name: &str) -> Option<&str> {
+        match &self.0 {
+            RespBody::Http(inner) => inner.headers.get(name).map(|v| v.as_str()),
+            RespBody::Raw(_) => None,
+        }
+    }
+    /// Read until `\n`, `None` when the response ends
+    ///
+    /// [Read][std::io::Read] of `ResponseLazy` waits until the buffer is full,
+    /// so a streaming response has to be consumed byte by byte
+    pub fn next_line(&mut self) -> Option<Result<String>> {
+        let mut line = Vec::new();
+        let inner = match &mut self.0 {
+            RespBody::Http(inner) => inner,
+            RespBody::Raw(inner) => {
+                return match inner.read_until(b'\n', &mut line) {
+                    Ok(0) => None,
+                    Ok(_) => {
+                        if line.last() == Some(&b'\n') {
+                            line.pop();
+                        }
+                        Some(utf8_line(line))
+                    }
+                    Err(e) => Some(Err(e.into())),
+                };
+            }
+        };
+        for res in inner.by_ref() {
+            match res {
+                Ok((b'\n', _)) => return Some(utf8_line(line)),
+                Ok((b, _)) => line.push(b),
+                Err(e) => return Some(Err(ApiError::from(e))),
+            }
+        }
+        if line.is_empty() {
+            None
+        } else {
+            Some(utf8_line(line))
+        }
+    }
+}
+
+fn utf8_line(line: Vec<u8>) -> Result<String> {
+    String::from_utf8(line)
+        .map_err(|e| ApiError::Request(minreq::Error::InvalidUtf8InBody(e.utf8_error())))
+}
+
+/// Streaming endpoints send one json object per line, e.g. `/traffic`
+pub struct JsonStream<T> {
+    resp: Resp,
+    _t: std::marker::PhantomData<T>,
 }
+impl<T: serde::de::DeserializeOwned> Iterator for JsonStream<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.resp.next_line()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => return Some(serde_json::from_str(&line).map_err(ApiError::from)),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+/// How requests reach the controller
+#[derive(Clone)]
+enum Transport {
+    // plain http to `api`
+    Minreq,
+    // `external-controller-unix`
+    Unix(std::path::PathBuf),
+    // `external-controller-tls`
+    Tls(crate::tls::TlsConnector),
+}
+
+#[derive(Clone)]
 pub struct ClashUtil {
     api: String,
+    transport: Transport,
     secret: String,
     pub proxy_addr: String,
     clash_ua: String,
+    net: NetConfig,
 }
 
 impl ClashUtil {
-    pub fn new(controller_api: String, secret: String, proxy_addr: String, clash_ua: String) -> Self {
+    pub fn new(
+        controller_api: String,
+        secret: String,
+        proxy_addr: String,
+        clash_ua: String,
+        net: NetConfig,
+    ) -> Self {
         Self {
             api: controller_api,
+            transport: Transport::Minreq,
+            secret,
+            proxy_addr,
+            clash_ua,
+            net,
+        }
+    }
+    /// Talk to the controller through `external-controller-unix`
+    pub fn new_unix(
+        socket: std::path::PathBuf,
+        secret: String,
+        proxy_addr: String,
+        clash_ua: String,
+        net: NetConfig,
+    ) -> Self {
+        Self {
+            api: "http://localhost".to_string(),
+            transport: Transport::Unix(socket),
+            secret,
+            proxy_addr,
+            clash_ua,
+            net,
+        }
+    }
+    /// Talk to the controller through `external-controller-tls`, `addr` is like `127.0.0.1:9443`
+    pub fn new_tls(
+        addr: &str,
+        trust: &crate::TlsTrust,
+        secret: String,
+        proxy_addr: String,
+        clash_ua: String,
+        net: NetConfig,
+    ) -> Result<Self> {
+        let (host, port) = addr
+            .rsplit_once(':')
+            .and_then(|(h, p)| Some((h, p.parse::<u16>().ok()?)))
+            .ok_or(ApiError::InvalidInput("Invalid controller address"))?;
+        Ok(Self {
+            api: format!("https://{addr}"),
+            transport: Transport::Tls(crate::tls::TlsConnector::new(host, port, trust)?),
             secret,
             proxy_addr,
             clash_ua,
+            net,
+        })
+    }
+    /// Send with our own client, `None` if minreq should do it
+    fn send_raw(
+        &self,
+        method: &str,
+        sub_url: &str,
+        payload: Option<&str>,
+        timeout: Option<std::time::Duration>,
+    ) -> Option<std::io::Result<crate::http1::Response>> {
+        match &self.transport {
+            Transport::Minreq => None,
+            Transport::Unix(socket) => Some(crate::unix::send(
+                socket,
+                method,
+                sub_url,
+                &self.secret,
+                payload,
+                timeout,
+            )),
+            Transport::Tls(tls) => Some(tls.send(method, sub_url, &self.secret, payload, timeout)),
         }
     }
     fn request(
@@ -86,6 +237,57 @@ impl ClashUtil {
         sub_url: &str,
         payload: Option<String>,
     ) -> Result<String> {
+        // the others might have been done before the error
+        let retries = if method == Method::Get {
+            self.net.controller_retries
+        } else {
+            0
+        };
+        self.retry(retries, || {
+            self.request_with_timeout(
+                method.clone(),
+                sub_url,
+                payload.clone(),
+                self.net.controller_timeout,
+            )
+        })
+    }
+    /// Call `f` again on [transient][ApiError::is_transient] errors, up to `retries` times
+    fn retry<T>(&self, retries: u32, f: impl Fn() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) if attempt < retries && e.is_transient() => {
+                    std::thread::sleep(self.net.backoff(attempt));
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+    /// A single attempt, `timeout` in seconds
+    fn request_with_timeout(
+        &self,
+        method: minreq::Method,
+        sub_url: &str,
+        payload: Option<String>,
+        timeout: u64,
+    ) -> Result<String> {
+        if let Some(resp) = self.send_raw(
+            &method.to_string(),
+            sub_url,
+            payload.as_deref(),
+            Some(std::time::Duration::from_secs(timeout)),
+        ) {
+            let resp = resp?;
+            let (code, reason) = (resp.status_code, resp.reason_phrase.clone());
+            let body = resp.into_string()?;
+            return if (200..300).contains(&code) {
+                Ok(body)
+            } else {
+                Err(ApiError::from_status(code, &reason, &body))
+            };
+        }
         let mut req = minreq::Request::new(method, self.api.to_owned() + sub_url);
         if let Some(kv) = payload {
             req = req.with_body(kv);
@@ -93,355 +295,581 @@ impl ClashUtil {
         if !self.secret.is_empty() {
             req = req.with_header("Authorization", format!("Bearer {0}", self.secret));
         }
-        req.with_timeout(TIMEOUT.into())
-            .send()
-            .and_then(|r| r.as_str().map(|s| s.to_owned()))
-            .map_err(process_err)
+        let resp = req.with_timeout(timeout).send().map_err(ApiError::from)?;
+        let body = resp.as_str().map(|s| s.to_owned()).map_err(ApiError::from)?;
+        if (200..300).contains(&resp.status_code) {
+            Ok(body)
+        } else {
+            Err(ApiError::from_status(resp.status_code, &resp.reason_phrase, &body))
+        }
+    }
+    fn request_json<T: serde::de::DeserializeOwned>(&self, sub_url: &str) -> Result<T> {
+        self.request(Method::Get, sub_url, None)
+            .and_then(|s| serde_json::from_str(&s).map_err(ApiError::from))
+    }
+    /// The core keeps the connection open, a read without anything new for
+    /// [STREAM_IDLE_TIMEOUT] yields an error of [is_timeout][ApiError::is_timeout]
+    fn stream<T>(&self, sub_url: &str) -> Result<JsonStream<T>> {
+        let resp = match self.send_raw("GET", sub_url, None, Some(STREAM_IDLE_TIMEOUT)) {
+            Some(resp) => resp?,
+            // the timeout of minreq is for the whole response
+            None => crate::http1::send(
+                self.api.trim_start_matches("http://"),
+                "GET",
+                sub_url,
+                &self.secret,
+                None,
+                Some(STREAM_IDLE_TIMEOUT),
+            )?,
+        };
+        if !(200..300).contains(&resp.status_code) {
+            let (code, reason) = (resp.status_code, resp.reason_phrase.clone());
+            let body = resp.into_string().unwrap_or_default();
+            return Err(ApiError::from_status(code, &reason, &body));
+        }
+        Ok(JsonStream {
+            resp: Resp(RespBody::Raw(resp.body)),
+            _t: std::marker::PhantomData,
+        })
     }
-    pub fn restart(&self, payload: Option<String>) -> Result<String> {
+    /// The core restarts itself with the same binary
+    pub fn restart(&self, payload: Option<String>) -> Result<()> {
         self.request(
             Method::Post,
             "/restart",
             Some(payload.unwrap_or(DEFAULT_PAYLOAD.to_string())),
         )
+        .map(|_| ())
     }
-    pub fn version(&self) -> Result<String> {
-        self.request(Method::Get, "/version", None)
+    pub fn version(&self) -> Result<ClashVersion> {
+        self.request_json("/version")
     }
-    pub fn config_get(&self) -> Result<String> {
-        self.request(Method::Get, "/configs", None)
+    /// The running config, which might differ from the file after `config_patch`
+    pub fn config_get(&self) -> Result<ClashConfig> {
+        self.request_json("/configs")
     }
     pub fn config_reload(&self, payload: String) -> Result<()> {
         self.request(Method::Put, "/configs?force=true", Some(payload))
             .map(|_| ())
     }
+    /// Fetch `url` as the core does, with its user agent
+    ///
+    /// Fail on 5xx once out of retries, the other statuses are up to the caller
     pub fn mock_clash_core<S: Into<minreq::URL>>(&self, url: S, with_proxy: bool) -> Result<Resp> {
+        self.get_lazy(url, with_proxy)
+            .map(|r| Resp(RespBody::Http(r)))
+    }
+    fn get_lazy<S: Into<minreq::URL>>(&self, url: S, with_proxy: bool) -> Result<minreq::ResponseLazy> {
         let mut request = minreq::get(url)
             .with_header("user-agent", self.clash_ua.clone())
-            .with_timeout(TIMEOUT.into());
+            .with_timeout(self.net.download_timeout);
 
         if with_proxy {
-            request = request.with_proxy(minreq::Proxy::new(self.proxy_addr.clone()).map_err(process_err)?);
+            request = request.with_proxy(minreq::Proxy::new(self.proxy_addr.clone()).map_err(ApiError::from)?);
         }
 
-        request.send_lazy().map(Resp).map_err(process_err)
-    }
-    pub fn config_patch(&self, payload: String) -> Result<String> {
-        self.request(Method::Patch, "/configs", Some(payload))
+        self.retry(self.net.download_retries, || {
+            let resp = request.clone().send_lazy().map_err(ApiError::from)?;
+            if resp.status_code >= 500 {
+                return Err(ApiError::from_status(resp.status_code, &resp.reason_phrase, ""));
+            }
+            Ok(resp)
+        })
+    }
+    /// Same as [mock_clash_core][ClashUtil::mock_clash_core], but fail if the status code is not 2xx
+    pub fn download<S: Into<minreq::URL>>(&self, url: S, with_proxy: bool) -> Result<Resp> {
+        let resp = self.get_lazy(url, with_proxy)?;
+        if (200..300).contains(&resp.status_code) {
+            Ok(Resp(RespBody::Http(resp)))
+        } else {
+            Err(ApiError::from_status(resp.status_code, &resp.reason_phrase, ""))
+        }
     }
-
-    /*** update_providers_with_api
-    pub fn update_providers(&self, provider_type: ProfileSectionType) -> Result<Vec<(String, Result<String>)>> {
-        self.extract_net_providers(provider_type).and_then(|names| self.update_providers_helper(names, provider_type))
+    /// Latest release of MetaCubeX/meta-rules-dat
+    #[cfg(feature = "github_api")]
+    pub fn geo_release(&self, with_proxy: bool) -> Result<crate::GithubApi> {
+        self.github_release(GEO_URI, with_proxy)
     }
-
-    pub fn update_providers_helper(&self, provider_names: Vec<String>, provider_type: ProfileSectionType) -> Result<Vec<(String, Result<String>)>> {
-        let mut result = Vec::<(String, Result<String>)>::new();
-        for name in provider_names {
-            let sub_url = format!("/providers/{}/{}", provider_str_in_api(provider_type).unwrap(), name);
-            result.push((name, self.request(Method::Put, sub_url.as_str(), None)));
+    /// `url` is like `https://api.github.com/repos/{owner}/{repo}/releases/latest`
+    #[cfg(feature = "github_api")]
+    pub fn github_release(&self, url: &str, with_proxy: bool) -> Result<crate::GithubApi> {
+        let mut request = minreq::get(url)
+            .with_header("user-agent", USER_AGENT)
+            .with_timeout(self.net.download_timeout);
+        if with_proxy {
+            request = request.with_proxy(minreq::Proxy::new(self.proxy_addr.clone()).map_err(ApiError::from)?);
         }
-        Ok(result)
-    }
-
-    pub fn extract_net_providers(&self, provider_type: ProfileSectionType) -> Result<Vec<String>>{
-        let sub_url = format!("/providers/{}", provider_str_in_api(provider_type).unwrap());
-        let response_str = self.request(Method::Get, sub_url.as_str(), None)?;
-
-        let json_data: serde_json::Value = serde_json::from_str(response_str.as_str())?;
-        let mut net_providers = Vec::new();
-        if let Some(providers) = json_data["providers"].as_object() {
-            let provider_type_str = match provider_type {
-                ProfileSectionType::ProxyProvider => Some("Proxy"),
-                ProfileSectionType::RuleProvider => Some("Rule"),
-                _ => None,
-            };
-            for (_, provider) in providers.iter() {
-                if let (Some(p_name), Some(p_type), Some(vehicle_type)) = (
-                    provider.get("name").and_then(serde_json::Value::as_str),
-                    provider.get("type").and_then(serde_json::Value::as_str),
-                    provider.get("vehicleType").and_then(serde_json::Value::as_str),
-                ) {
-                    if vehicle_type == "HTTP" {
-                        if Some(p_type) == provider_type_str {
-                            net_providers.push(p_name.to_string());
-                        }
-                    }
-                }
+        let resp = self.retry(self.net.download_retries, || {
+            let resp = request.clone().send().map_err(ApiError::from)?;
+            if !(200..300).contains(&resp.status_code) {
+                return Err(ApiError::from_status(
+                    resp.status_code,
+                    &resp.reason_phrase,
+                    resp.as_str().unwrap_or_default(),
+                ));
             }
-        }
-
-        Ok(net_providers)
+            Ok(resp)
+        })?;
+        serde_json::from_str(resp.as_str().map_err(ApiError::from)?).map_err(ApiError::from)
     }
-
-    // Sometime mihomo updated the provider but not update it to the file.
-    pub fn extract_provider_utimes_with_api(&self, provider_type: ProfileSectionType) -> Result<Vec<(String, Option<std::time::SystemTime>)>>{
-        use chrono::{DateTime, Local};
-
-        let sub_url = format!("/providers/{}", provider_str_in_api(provider_type).unwrap());
-        let response_str = self.request(Method::Get, sub_url.as_str(), None)?;
-
-        let json_data: serde_json::Value = serde_json::from_str(response_str.as_str())?;
-        let mut net_providers = Vec::new();
-        if let Some(providers) = json_data["providers"].as_object() {
-            let provider_type_str = match provider_type {
-                ProfileSectionType::ProxyProvider => Some("Proxy"),
-                ProfileSectionType::RuleProvider => Some("Rule"),
-                _ => None,
-            };
-            for (_, provider) in providers.iter() {
-                if let (Some(p_name), Some(p_type), Some(vehicle_type), Some(time_str)) = (
-                    provider.get("name").and_then(serde_json::Value::as_str),
-                    provider.get("type").and_then(serde_json::Value::as_str),
-                    provider.get("vehicleType").and_then(serde_json::Value::as_str),
-                    provider.get("updatedAt").and_then(serde_json::Value::as_str),
-                ) {
-                    if vehicle_type == "HTTP" {
-                        if Some(p_type) == provider_type_str {
-                                let parsed_time = DateTime::parse_from_rfc3339(time_str);
-                                net_providers.push((
-                                    p_name.to_string(),
-                                    parsed_time.ok().map(|t| t.with_timezone(&Local).into())
-                                ));
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(net_providers)
+    /// Let the core download the geo data itself, which reloads the config once done
+    pub fn config_geo_update(&self) -> Result<()> {
+        self.request_with_timeout(Method::Post, "/configs/geo", None, self.net.download_timeout)
+            .map(|_| ())
     }
-
-    pub fn provider_str_in_api(section_type: ProfileSectionType) -> Option<String> {
-        match section_type {
-            ProfileSectionType::ProxyProvider => Some("proxies".to_string()),
-            ProfileSectionType::RuleProvider => Some("rules".to_string()),
-            _ => None,
-        }
+    /// Change some fields of the running config, the file is left as is
+    pub fn config_patch(&self, payload: String) -> Result<()> {
+        self.request(Method::Patch, "/configs", Some(payload))
+            .map(|_| ())
     }
-    ***/
-
-    #[cfg(target_feature = "deprecated")]
-    pub fn check_geo_update(
-        &self,
-        old_id: Option<&String>,
-        path: &std::path::Path,
-    ) -> Result<String> {
-        let info = self
-            .client
-            .get_or_init(Client::new)
-            .get(GEO_URI)
-            .header(reqwest::header::USER_AGENT, USER_AGENT)
-            .send()
-            .map_err(process_err)?
-            .text()
-            .map_err(process_err)?;
-        let info: crate::geo::GithubApi =
-            serde_json::from_str(info.as_str()).map_err(Error::from)?;
-        if info.check(old_id) {
-            let (assets, publist_at) = info.into();
-            let result: Vec<Error> = assets
-                .into_iter()
-                // .inspect(|e| println!("{e:?}"))
-                .map(|info| info.into())
-                // ignore sha check for now, though no future plan to add
-                .filter(|(name, _)| !name.ends_with("sha256sum"))
-                .filter_map(|(name, url)| {
-                    let path = path.join(name);
-                    self.client
-                        .get_or_init(Client::new)
-                        .get(url)
-                        .send()
-                        .map_err(process_err)
-                        .and_then(|dow| {
-                            std::fs::File::options()
-                                .create(true)
-                                .write(true)
-                                .open(path)
-                                .and_then(|mut file| {
-                                    file.write_all(dow.text().map_err(process_err)?.as_bytes())
-                                })
-                        })
-                        .err()
-                })
-                // .inspect(|e| println!("{e:?}"))
-                .collect();
-            if result.is_empty() {
-                Ok(publist_at)
-            } else {
-                Err(Error::new(ErrorKind::Other, format!("{result:?}")))
-            }
-        } else {
-            Ok("Already Up to dated".to_string())
-        }
+    pub fn proxies(&self) -> Result<ClashProxies> {
+        self.request_json("/proxies")
     }
-
-    /*
-    pub fn flush_fakeip(&self) -> Result<String, reqwest::Error> {
-        self.post("/cache/fakeip/flush", None)
-    }
-    pub fn provider(&self, is_rule: bool, name:Option<&String>, is_update: bool, is_check: bool) -> Result<String, reqwest::Error>{
-        //
-        if !is_rule{
-            let api = "/providers/proxies";
-            match name {
-                Some(v) => {
-                    if is_update{
-                        self.put(&format!("{}/{}", api, v), None)
-                    } else {
-                        if is_check{
-                            self.get(&format!("{}/{}/healthcheck", api, v), None)
-                        } else {
-                            self.get(&format!("{}/{}", api, v), None)
-                        }
-                    }
-                },
-                None => self.get(api, None),
-            }
-        } else {
-            let api = "/providers/rules";
-            match name {
-                Some(v) => self.put(&format!("{}/{}", api, v), None),
-                None => self.get(api, None)
-            }
-        }
+    /// Up/down rates, one message per second
+    pub fn traffic(&self) -> Result<JsonStream<Traffic>> {
+        self.stream("/traffic")
     }
-    pub fn update_geo(&self, payload:Option<&String>) -> Result<String, reqwest::Error>{
-        match payload {
-            Some(load) => self.post("/configs/geo", Some(load)),
-            None => self.post("/configs/geo", Some(&self.default_payload))
-        }
+    /// Memory usage of the core, one message per second
+    pub fn memory(&self) -> Result<JsonStream<Memory>> {
+        self.stream("/memory")
     }
-    pub fn log(&self) -> Result<String, reqwest::Error>{
-        self.get("/logs", None)
+    /// Logs of the core at `level` and above, sent as they are produced
+    pub fn logs(&self, level: LogLevel) -> Result<JsonStream<ClashLog>> {
+        self.stream(&format!("/logs?level={}", level))
     }
-    pub fn traffic(&self) -> Result<String, reqwest::Error>{
-        self.get(&"/traffic", None)
+    pub fn providers(&self, provider_type: ProfileSectionType) -> Result<ClashProviders> {
+        self.request_json(provider_type.provider_path()?)
     }
-    pub fn memory(&self) -> Result<String, reqwest::Error>{
-        self.get(&"/memory", None)
+    /// Let the core fetch the provider again
+    pub fn provider_update(&self, provider_type: ProfileSectionType, name: &str) -> Result<()> {
+        let sub_url = format!("{}/{}", provider_type.provider_path()?, encode_path(name));
+        self.request(Method::Put, &sub_url, None).map(|_| ())
     }
-    pub fn upgrade(&self, payload:Option<&String>) -> Result<String, reqwest::Error>{
-        match payload {
-            Some(load) => self.post("/upgrade", Some(load)),
-            None => self.post("/upgrade", Some(&self.default_payload))
-        }
+    /// Test the latency of all nodes in a proxy provider
+    pub fn provider_healthcheck(&self, name: &str) -> Result<()> {
+        let sub_url = format!(
+            "{}/{}/healthcheck",
+            ProfileSectionType::ProxyProvider.provider_path()?,
+            encode_path(name)
+        );
+        self.request(Method::Get, &sub_url, None).map(|_| ())
+    }
+    /// Resolve `name` with the resolver of the core, `record_type` is like `A` or `AAAA`
+    pub fn dns_query(&self, name: &str, record_type: &str) -> Result<DnsQuery> {
+        let sub_url = format!(
+            "/dns/query?name={}&type={}",
+            encode_path(name),
+            encode_path(record_type)
+        );
+        self.request_json(&sub_url)
     }
-    pub fn upgrade_ui(&self) -> Result<String, reqwest::Error>{
-        self.post("/upgrade/ui", None)
+    /// Drop the mappings of fake-ip
+    pub fn flush_fakeip(&self) -> Result<()> {
+        self.request(Method::Post, "/cache/fakeip/flush", None)
+            .map(|_| ())
     }
-    pub fn proxies(&self, name:Option<&String>, test_delay: bool) -> Result<String, reqwest::Error>{
-        let api = match name {
-            Some(v) => if test_delay {
-                format!("/proxies/{}/delay", v)
-            } else {
-                format!("/proxies/{}", v)
-            },
-            None => "/proxies/".to_string()
-        };
-        self.get(&api, None)
+    /// Drop the dns cache, not supported by old cores
+    pub fn flush_dns_cache(&self) -> Result<()> {
+        self.request(Method::Post, "/cache/dns/flush", None)
+            .map(|_| ())
     }
-    pub fn set_proxy(&self, name:&String) -> Result<String, reqwest::Error> {
-        self.put(&format!("/proxies/{}", name), None)
+    pub fn rules(&self) -> Result<ClashRules> {
+        self.request_json("/rules")
     }
-    pub fn rules(&self) -> Result<String, reqwest::Error>{
-        self.get("/rules", None)
+    pub fn connections(&self) -> Result<ClashConnections> {
+        self.request_json("/connections")
     }
-    pub fn connection(&self, is_close: bool, id:Option<usize>) -> Result<String, reqwest::Error>{
-        if !is_close {
-            self.get("/connections", None)
-        } else {
-            match
-                match id {
-                    Some(v) => self.client.delete(format!("/connections/{}", v)).send(),
-                    None => self.client.delete("/connections").send(),
-                }
-                {
-                Ok(r) => r.text(),
-                Err(e) => {
-                    log::error!("[ClashUtil] {} exec {} failed! {}", "patch", "/configs", e.status().unwrap());
-                    Err(e)
-                }
-            }
-        }
+    pub fn connection_close(&self, id: &str) -> Result<()> {
+        self.request(
+            Method::Delete,
+            &format!("/connections/{}", encode_path(id)),
+            None,
+        )
+        .map(|_| ())
     }
-    pub fn dns_resolve(&self, name:&String, _type:Option<&String>) -> Result<String, reqwest::Error>{
-        match _type {
-            Some(v) => self.get(&format!("/dns/query?name={}&type={}", name, v), None),
-            None => self.get(&format!("/dns/query?name={}", name), None),
-        }
+    pub fn connection_close_all(&self) -> Result<()> {
+        self.request(Method::Delete, "/connections", None)
+            .map(|_| ())
+    }
+    /// Test the latency of a node (or the selected node of a group), `timeout` in ms
+    pub fn proxy_delay(&self, name: &str, url: &str, timeout: u64) -> Result<u64> {
+        let sub_url = format!(
+            "/proxies/{}/delay?url={}&timeout={}",
+            encode_path(name),
+            encode_path(url),
+            timeout
+        );
+        let req_timeout = delay_req_timeout(timeout, self.net.controller_timeout);
+        self.request_with_timeout(Method::Get, &sub_url, None, req_timeout)
+            .and_then(|s| serde_json::from_str::<ProxyDelay>(&s).map_err(ApiError::from))
+            .map(|d| d.delay)
+    }
+    /// Test the latency of every member of a group, `timeout` in ms
+    ///
+    /// Members failed the test are absent in the result
+    pub fn group_delay(&self, name: &str, url: &str, timeout: u64) -> Result<HashMap<String, u64>> {
+        let sub_url = format!(
+            "/group/{}/delay?url={}&timeout={}",
+            encode_path(name),
+            encode_path(url),
+            timeout
+        );
+        let req_timeout = delay_req_timeout(timeout, self.net.controller_timeout);
+        self.request_with_timeout(Method::Get, &sub_url, None, req_timeout)
+            .and_then(|s| serde_json::from_str(&s).map_err(ApiError::from))
+    }
+    /// Select `name` in the proxy group `group`
+    pub fn proxy_select(&self, group: &str, name: &str) -> Result<()> {
+        let payload = serde_json::json!({ "name": name }).to_string();
+        self.request(
+            Method::Put,
+            &format!("/proxies/{}", encode_path(group)),
+            Some(payload),
+        )
+        .map(|_| ())
+    }
+
+    /// The core replaces its binary and restarts itself on success
+    pub fn upgrade(&self) -> Result<()> {
+        self.request_with_timeout(Method::Post, "/upgrade", None, self.net.download_timeout)
+            .map(|_| ())
+    }
+    /// Download the dashboard into `external-ui`
+    pub fn upgrade_ui(&self) -> Result<()> {
+        self.request_with_timeout(Method::Post, "/upgrade/ui", None, self.net.download_timeout)
+            .map(|_| ())
     }
-    */
 }
 #[cfg(test)]
 mod tests {
-    use super::{ClashUtil, ProfileSectionType};
-    fn sym() -> ClashUtil {
+    use super::ClashUtil;
+    use crate::mock::{MockController, MOCK_VERSION};
+    use crate::{Mode, ProfileSectionType};
+    fn sym(mock: &MockController) -> ClashUtil {
         ClashUtil::new(
-            "http://127.0.0.1:9090".to_string(),
+            mock.url(),
             "test".to_string(),
             "http://127.0.0.1:7890".to_string(),
             "clash.meta".to_string(),
+            Default::default(),
         )
     }
     #[test]
+    fn encode_path_test() {
+        assert_eq!(super::encode_path("Auto"), "Auto");
+        assert_eq!(super::encode_path("HK 01/a"), "HK%2001%2Fa");
+        assert_eq!(super::encode_path("节点"), "%E8%8A%82%E7%82%B9");
+    }
+    #[test]
+    fn json_stream_test() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = s.read(&mut buf).unwrap();
+            s.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .unwrap();
+            // a message might be split into several chunks
+            for chunk in ["{\"up\":1,\"down\":2}\n{\"up\":3,", "\"down\":4}\n"] {
+                write!(s, "{:x}\r\n{}\r\n", chunk.len(), chunk).unwrap();
+            }
+            s.write_all(b"0\r\n\r\n").unwrap();
+        });
+        let sym = ClashUtil::new(
+            format!("http://{addr}"),
+            String::new(),
+            String::new(),
+            String::new(),
+            Default::default(),
+        );
+        let traffic: Vec<(u64, u64)> = sym
+            .traffic()
+            .unwrap()
+            .map(|t| t.map(|t| (t.up, t.down)).unwrap())
+            .collect();
+        assert_eq!(traffic, [(1, 2), (3, 4)]);
+    }
+    #[test]
     fn version_test() {
-        let sym = sym();
-        println!("{}", sym.version().unwrap());
+        let mock = MockController::start("test");
+        let v = sym(&mock).version().unwrap();
+        assert!(v.meta);
+        assert_eq!(v.version, MOCK_VERSION);
+    }
+    #[test]
+    fn secret_test() {
+        let mock = MockController::start("another");
+        let e = sym(&mock).version().unwrap_err();
+        assert!(e.is_unauthorized(), "{e}");
+    }
+    #[test]
+    fn unix_socket_test() {
+        let socket = std::env::temp_dir().join(format!("clashtui_mock_{}.sock", std::process::id()));
+        let mock = MockController::start_unix(socket.clone(), "test");
+        let sym = ClashUtil::new_unix(
+            socket,
+            "test".to_string(),
+            String::new(),
+            String::new(),
+            Default::default(),
+        );
+        assert_eq!(sym.version().unwrap().version, MOCK_VERSION);
+        sym.config_patch(r#"{"mode": "direct"}"#.to_string()).unwrap();
+        assert_eq!(mock.config()["mode"], "direct");
+        assert!(sym.proxy_select("Missing", "DIRECT").unwrap_err().is_not_found());
+        // chunked
+        let traffic: Vec<u64> = sym.traffic().unwrap().map(|t| t.unwrap().up).collect();
+        assert_eq!(traffic, [1, 3]);
+    }
+    #[test]
+    fn tls_test() {
+        use crate::mock::{MOCK_CA_PEM, MOCK_CERT_SHA256};
+        use crate::TlsTrust;
+        let mock = MockController::start_tls("test");
+        let tls = |trust: &TlsTrust| {
+            ClashUtil::new_tls(
+                &mock.addr(),
+                trust,
+                "test".to_string(),
+                String::new(),
+                String::new(),
+                Default::default(),
+            )
+        };
+
+        let ca = std::env::temp_dir().join(format!("clashtui_mock_ca_{}.pem", std::process::id()));
+        std::fs::write(&ca, MOCK_CA_PEM).unwrap();
+        let sym = tls(&TlsTrust::CaBundle(ca.clone())).unwrap();
+        let _ = std::fs::remove_file(&ca);
+        assert_eq!(sym.version().unwrap().version, MOCK_VERSION);
+        sym.config_patch(r#"{"mode": "direct"}"#.to_string()).unwrap();
+        assert_eq!(mock.config()["mode"], "direct");
+        let traffic: Vec<u64> = sym.traffic().unwrap().map(|t| t.unwrap().up).collect();
+        assert_eq!(traffic, [1, 3]);
+
+        let sym = tls(&TlsTrust::Fingerprint(MOCK_CERT_SHA256.to_lowercase())).unwrap();
+        assert_eq!(sym.version().unwrap().version, MOCK_VERSION);
+        // self-signed for the public roots
+        assert!(tls(&TlsTrust::System).unwrap().version().is_err());
+        let wrong = MOCK_CERT_SHA256.replace("AD:CA", "00:00");
+        assert!(tls(&TlsTrust::Fingerprint(wrong)).unwrap().version().is_err());
+        assert!(tls(&TlsTrust::Fingerprint("AD:CA".to_string())).is_err());
     }
     #[test]
     fn config_get_test() {
-        let sym = sym();
-        println!("{}", sym.config_get().unwrap());
+        let mock = MockController::start("test");
+        let cfg = sym(&mock).config_get().unwrap();
+        assert!(matches!(cfg.mode, Mode::Rule));
+        assert!(!cfg.tun.enable);
     }
     #[test]
     fn config_patch_test() {
-        let sym = sym();
-        println!("{}", sym.config_patch("".to_string()).unwrap());
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        sym.config_patch(r#"{"mode": "global", "tun": {"enable": true}}"#.to_string())
+            .unwrap();
+        let cfg = sym.config_get().unwrap();
+        assert!(matches!(cfg.mode, Mode::Global));
+        assert!(cfg.tun.enable);
+        // the others are kept
+        assert_eq!(mock.config()["tun"]["stack"], "Mixed");
+        let e = sym.config_patch("".to_string()).unwrap_err();
+        assert_eq!(e.status_code(), Some(400));
     }
     #[test]
     fn config_reload_test() {
-        let sym = sym();
-        sym.config_reload(super::DEFAULT_PAYLOAD.to_string())
+        let mock = MockController::start("test");
+        sym(&mock)
+            .config_reload(super::DEFAULT_PAYLOAD.to_string())
+            .unwrap();
+        assert!(mock.hits().contains(&"PUT /configs".to_string()));
+    }
+    #[test]
+    fn proxies_test() {
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        let proxies = sym.proxies().unwrap();
+        let groups: Vec<&str> = proxies.groups().iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(groups, ["Proxy", "GLOBAL"]);
+        sym.proxy_select("Proxy", "node-b").unwrap();
+        assert_eq!(mock.selected("Proxy").as_deref(), Some("node-b"));
+        assert!(sym.proxy_select("Proxy", "node-c").is_err());
+        assert!(sym.proxy_select("Missing", "DIRECT").unwrap_err().is_not_found());
+    }
+    #[test]
+    fn delay_test() {
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        let url = "https://www.gstatic.com/generate_204";
+        assert_eq!(sym.proxy_delay("node-a", url, 5000).unwrap(), 42);
+        assert!(sym.proxy_delay("REJECT", url, 5000).is_err());
+        let delays = sym.group_delay("GLOBAL", url, 5000).unwrap();
+        assert_eq!(delays.get("Proxy"), Some(&42));
+        assert!(!delays.contains_key("REJECT"));
+    }
+    #[test]
+    fn traffic_test() {
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        let traffic: Vec<(u64, u64)> = sym
+            .traffic()
+            .unwrap()
+            .map(|t| t.map(|t| (t.up, t.down)).unwrap())
+            .collect();
+        assert_eq!(traffic, [(1, 2), (3, 4)]);
+        let memory = sym.memory().unwrap().next().unwrap().unwrap();
+        assert_eq!(memory.inuse, 1024);
+    }
+    #[test]
+    fn logs_test() {
+        let mock = MockController::start("test");
+        let log = sym(&mock)
+            .logs(crate::LogLevel::Debug)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(log.payload, "mock log");
+        assert!(mock.hits().contains(&"GET /logs".to_string()));
+
+        // a quiet stream times out and reads on
+        mock.idle_streams(std::time::Duration::from_millis(1500));
+        let mut logs = sym(&mock).logs(crate::LogLevel::Debug).unwrap();
+        assert!(logs.next().unwrap().is_ok());
+        assert!(logs.next().unwrap().unwrap_err().is_timeout());
+        assert!(logs.next().is_none());
+    }
+    #[test]
+    fn providers_test() {
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        let providers = sym.providers(ProfileSectionType::ProxyProvider).unwrap();
+        assert_eq!(providers.providers["sub"].count(), 2);
+        assert!(providers.providers["default"].updated_at().is_none());
+        let rules = sym.providers(ProfileSectionType::RuleProvider).unwrap();
+        assert!(rules.providers["reject"].is_rule());
+        assert!(sym.providers(ProfileSectionType::Profile).is_err());
+
+        sym.provider_update(ProfileSectionType::ProxyProvider, "sub")
             .unwrap();
+        sym.provider_healthcheck("sub").unwrap();
+        assert!(sym
+            .provider_update(ProfileSectionType::RuleProvider, "missing")
+            .unwrap_err()
+            .is_not_found());
+    }
+    #[test]
+    fn dns_query_test() {
+        let mock = MockController::start("test");
+        let query = sym(&mock).dns_query("example.com", "A").unwrap();
+        assert_eq!(query.status_str(), "NOERROR");
+        assert_eq!(query.answer[0].name, "example.com.");
+        assert_eq!(query.answer[0].type_str(), "A");
+    }
+    #[test]
+    fn flush_cache_test() {
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        sym.flush_fakeip().unwrap();
+        match sym.flush_dns_cache() {
+            Err(e) if !e.is_not_found() => panic!("{e}"),
+            _ => (),
+        }
+    }
+    #[test]
+    fn rules_test() {
+        let mock = MockController::start("test");
+        let rules = sym(&mock).rules().unwrap();
+        assert_eq!(rules.rules.len(), 3);
+        assert_eq!(rules.rules[0].size(), Some(3));
+        assert_eq!(rules.rules[1].size(), None);
+    }
+    #[test]
+    fn connections_test() {
+        let mock = MockController::start("test");
+        let sym = sym(&mock);
+        let conns = sym.connections().unwrap();
+        assert_eq!(conns.connections[0].destination(), "example.com:443");
+        sym.connection_close_all().unwrap();
+        assert!(sym.connections().unwrap().connections.is_empty());
     }
     #[test]
     fn mock_clash_core_test() {
-        let sym = sym();
-        let r = sym.mock_clash_core("https://www.google.com", true).unwrap();
-        let mut tf = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open("test")
+        let mock = MockController::start("test");
+        mock.serve("/sub.yaml", 200, &[], "proxies: []\n");
+        let r = sym(&mock)
+            .mock_clash_core(format!("{}/sub.yaml", mock.url()), false)
+            .unwrap();
+        let mut buf = Vec::new();
+        r.copy_to(&mut buf).unwrap();
+        assert_eq!(buf, b"proxies: []\n");
+    }
+    #[test]
+    fn download_test() {
+        let mock = MockController::start("test");
+        mock.serve("/missing.dat", 404, &[], "");
+        let e = sym(&mock)
+            .download(format!("{}/missing.dat", mock.url()), false)
+            .err()
             .unwrap();
-        r.copy_to(&mut tf).unwrap();
-        drop(tf);
-        std::fs::remove_file("test").unwrap();
+        assert!(e.is_not_found());
     }
     #[test]
-    #[cfg(target_feature = "deprecated")]
-    fn test_geo_update() {
-        let mut flag = true;
+    fn retry_test() {
+        let mock = MockController::start("test");
+        mock.serve("/busy.yaml", 503, &[], "");
+        mock.serve("/missing.yaml", 404, &[], "");
+        let net = crate::NetConfig {
+            download_retries: 2,
+            retry_backoff: 1,
+            ..Default::default()
+        };
+        let sym = ClashUtil::new(mock.url(), "test".to_string(), String::new(), String::new(), net);
+        let e = sym
+            .mock_clash_core(format!("{}/busy.yaml", mock.url()), false)
+            .err()
+            .unwrap();
+        assert_eq!(e.status_code(), Some(503));
+        // 4xx is not retried
+        let _ = sym.download(format!("{}/missing.yaml", mock.url()), false);
+        let hits = mock.hits();
+        assert_eq!(hits.iter().filter(|h| *h == "GET /busy.yaml").count(), 3);
+        assert_eq!(hits.iter().filter(|h| *h == "GET /missing.yaml").count(), 1);
+    }
+    #[test]
+    fn timeout_test() {
+        // accepted by the kernel but never answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let net = crate::NetConfig {
+            controller_timeout: 1,
+            ..Default::default()
+        };
         let sym = ClashUtil::new(
-            "http://127.0.0.1:9090".to_string(),
-            "http://127.0.0.1:7890".to_string(),
+            format!("http://{}", listener.local_addr().unwrap()),
+            String::new(),
+            String::new(),
+            String::new(),
+            net,
         );
-        let exe_dir = std::env::current_dir().unwrap();
-        println!("{exe_dir:?}");
-        let path = exe_dir.join("tmp");
-        if !path.is_dir() {
-            std::fs::create_dir_all(&path).unwrap()
-        }
-        println!(
-            "result:{}",
-            match sym.check_geo_update(None, &path) {
-                Ok(r) => r,
-                Err(e) => {
-                    flag = false;
-                    format!("{e:?}")
-                }
-            }
-        );
-        assert!(flag)
+        let start = std::time::Instant::now();
+        assert!(sym.version().unwrap_err().is_transient());
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+    #[test]
+    #[cfg(feature = "github_api")]
+    fn github_release_test() {
+        let mock = MockController::start("test");
+        let release = r#"{
+            "id": 1234, "name": "Release 1", "tag_name": "202405010000",
+            "draft": false, "prerelease": false, "published_at": "2024-05-01T00:00:00Z",
+            "assets": [{"name": "geosite.dat", "browser_download_url": "http://127.0.0.1/geosite.dat"}]
+        }"#;
+        mock.serve("/repos/MetaCubeX/meta-rules-dat/releases/latest", 200, &[], release);
+        let url = format!("{}/repos/MetaCubeX/meta-rules-dat/releases/latest", mock.url());
+        let release = sym(&mock).github_release(&url, false).unwrap();
+        assert_eq!(release.id, "1234");
+        assert!(release.assets.iter().any(|a| a.name == "geosite.dat"));
     }
 }
@@ -1,20 +1,51 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+/// The running config, as `GET /configs` answers
+///
+/// Keys follow the config file, except for `sniffing` which is `sniffer.enable` there
 #[derive(Debug, Serialize, Deserialize, Default)]
 #[serde(rename_all = "kebab-case")]
 #[serde(default)]
 pub struct ClashConfig {
-    // pub mixed_port: usize,
+    pub port: u16,
+    pub socks_port: u16,
+    pub redir_port: u16,
+    pub tproxy_port: u16,
+    pub mixed_port: u16,
+    pub allow_lan: bool,
+    pub bind_address: String,
+    #[serde(deserialize_with = "null_as_default")]
+    pub lan_allowed_ips: Vec<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub lan_disallowed_ips: Vec<String>,
+    #[serde(deserialize_with = "null_as_default")]
+    pub skip_auth_prefixes: Vec<String>,
     pub mode: Mode,
-    // pub log_level: LogLevel,
-    // pub allow_lan: bool,
-    // bind_address: String,
-    // pub ipv6: bool,
-    // pub secret: String,
-    // tcp_concurrent: bool,
-    // pub external_controller: String,
-    // pub global_client_fingerprint: String,
+    pub log_level: LogLevel,
+    pub ipv6: bool,
+    pub interface_name: String,
+    pub routing_mark: u32,
+    pub sniffing: bool,
+    pub tcp_concurrent: bool,
+    pub unified_delay: bool,
+    pub find_process_mode: String,
+    pub global_client_fingerprint: String,
+    pub global_ua: String,
+    pub geodata_mode: bool,
+    pub geo_auto_update: bool,
+    pub geo_update_interval: u64,
+    pub keep_alive_interval: u64,
+    pub keep_alive_idle: u64,
+    pub disable_keep_alive: bool,
     pub tun: TunConfig,
 }
+/// Go answers `null` for empty lists
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
 impl std::str::FromStr for ClashConfig {
     type Err = std::fmt::Error;
 
@@ -49,16 +80,28 @@ impl From<Mode> for String {
         val.to_string()
     }
 }
-// #[derive(Debug, Serialize, Deserialize, Default)]
-// #[serde(rename_all = "lowercase")]
-// pub enum LogLevel {
-//     Silent,
-//     Error,
-//     Warning,
-//     #[default]
-//     Info,
-//     Debug,
-// }
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Silent,
+    Error,
+    Warning,
+    #[default]
+    Info,
+    Debug,
+}
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let x = match self {
+            LogLevel::Silent => "silent",
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warning",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        };
+        write!(f, "{}", x)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct TunConfig {
@@ -86,3 +129,34 @@ impl std::fmt::Display for TunStack {
         write!(f, "{}", val)
     }
 }
+impl From<TunStack> for String {
+    fn from(val: TunStack) -> Self {
+        val.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClashConfig, LogLevel, Mode};
+    #[test]
+    fn parse_config() {
+        let cfg: ClashConfig = serde_json::from_str(
+            r#"{
+                "port": 0, "socks-port": 0, "redir-port": 0, "tproxy-port": 0, "mixed-port": 7890,
+                "allow-lan": true, "bind-address": "*", "authentication": null,
+                "skip-auth-prefixes": ["127.0.0.1/32"], "lan-allowed-ips": ["0.0.0.0/0"],
+                "lan-disallowed-ips": null, "mode": "rule", "log-level": "warning", "ipv6": true,
+                "interface-name": "", "sniffing": true, "tcp-concurrent": true,
+                "find-process-mode": "strict",
+                "tun": {"enable": false, "device": "", "stack": "gVisor"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(cfg.mixed_port, 7890);
+        assert!(cfg.allow_lan && cfg.ipv6 && cfg.sniffing && cfg.tcp_concurrent);
+        assert!(cfg.lan_disallowed_ips.is_empty());
+        assert_eq!(cfg.lan_allowed_ips, ["0.0.0.0/0"]);
+        assert!(matches!(cfg.mode, Mode::Rule));
+        assert_eq!(cfg.log_level, LogLevel::Warning);
+    }
+}
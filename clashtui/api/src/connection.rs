@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+/// Response of `GET /connections`
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct ClashConnections {
+    pub download_total: u64,
+    pub upload_total: u64,
+    // `null` when there is no connection
+    #[serde(deserialize_with = "null_as_default")]
+    pub connections: Vec<ClashConnection>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct ClashConnection {
+    pub id: String,
+    pub metadata: ConnMetadata,
+    pub upload: u64,
+    pub download: u64,
+    pub start: String,
+    /// From the node to the first group
+    pub chains: Vec<String>,
+    pub rule: String,
+    pub rule_payload: String,
+}
+impl ClashConnection {
+    /// `host:port`, fallback to the destination ip
+    pub fn destination(&self) -> String {
+        let host = if self.metadata.host.is_empty() {
+            self.metadata.destination_ip.as_str()
+        } else {
+            self.metadata.host.as_str()
+        };
+        format!("{}:{}", host, self.metadata.destination_port)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct ConnMetadata {
+    pub network: String,
+    #[serde(rename = "type")]
+    pub conn_type: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: String,
+    pub source_port: String,
+    pub destination_port: String,
+    pub host: String,
+    pub process: String,
+    pub process_path: String,
+}
+
+fn null_as_default<'de, D, T>(d: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Option::<T>::deserialize(d).map(Option::unwrap_or_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClashConnections;
+    #[test]
+    fn parse_connections() {
+        let conns: ClashConnections = serde_json::from_str(
+            r#"{"downloadTotal":2048,"uploadTotal":1024,"connections":[{"id":"c1","metadata":{"network":"tcp","type":"HTTP","sourceIP":"127.0.0.1","destinationIP":"1.1.1.1","sourcePort":"5000","destinationPort":"443","host":"example.com","processPath":"/usr/bin/curl"},"upload":10,"download":20,"start":"2024-05-01T10:00:00Z","chains":["node-a","Entry"],"rule":"Match","rulePayload":""}]}"#,
+        )
+        .unwrap();
+        assert_eq!(conns.connections[0].destination(), "example.com:443");
+        assert_eq!(conns.connections[0].metadata.process_path, "/usr/bin/curl");
+        let empty: ClashConnections =
+            serde_json::from_str(r#"{"downloadTotal":0,"uploadTotal":0,"connections":null}"#)
+                .unwrap();
+        assert!(empty.connections.is_empty());
+    }
+}
@@ -1,76 +1,439 @@
-const MIHOMO: &str = "https://api.github.com/repos/MetaCubeX/mihomo/releases/latest";
-const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
-
-fn dl<S: Into<minreq::URL>>(url: S) -> minreq::ResponseLazy {
-    minreq::get(url)
-        .with_header("user-agent", USER_AGENT)
-        .with_timeout(120)
-        .send_lazy()
-        .unwrap()
+//! Install the mihomo core from its github releases
+use crate::{ClashUtil, GithubApi};
+use flate2::read::GzDecoder;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Error, ErrorKind},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Latest release of MetaCubeX/mihomo
+pub const MIHOMO_RELEASE: &str = "https://api.github.com/repos/MetaCubeX/mihomo/releases/latest";
+// sha256 of every asset, in the form of `sha256sum`
+const CHECKSUMS: &str = "checksums.txt";
+
+/// Where to get the core and where to put it
+#[derive(Debug, Clone)]
+pub struct MihomoInstall {
+    /// Like [MIHOMO_RELEASE]
+    pub release_url: String,
+    /// Prefixed to the download urls, like `https://ghproxy.net/`
+    pub mirror: String,
+    pub with_proxy: bool,
+    /// The binary to replace, the previous one is kept as `{core_path}.bak`
+    pub core_path: PathBuf,
 }
-fn dl_mihomo<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<std::path::PathBuf> {
-    let apinfo: super::GithubApi = serde_json::from_reader(dl(MIHOMO))?;
-
-    let name = {
-        // it should be ok to do at compile-time, since 64bit platform can run 32bit software
-        #[cfg(target_os = "linux")]
-        let os = "linux";
-        #[cfg(target_os = "windows")]
-        let os = "windows";
-        #[cfg(target_arch = "x86_64")]
-        let arch = "amd64";
-        #[cfg(target_arch = "x86")]
-        let arch = "386";
-        let compat = false;
-        let oldgo = false;
-        let tag_name = apinfo.tag_name;
-        let mut s = format!("mihomo-{os}-{arch}");
-        if compat {
-            s.push_str("-compatible")
-        }
-        if oldgo {
-            s.push_str("-go120")
+
+/// What [install_mihomo][ClashUtil::install_mihomo] did
+#[derive(Debug)]
+pub struct Installed {
+    pub tag_name: String,
+    pub asset: String,
+    /// The previous binary, see [rollback_mihomo]
+    pub backup: Option<PathBuf>,
+}
+
+impl MihomoInstall {
+    pub fn new<P: Into<PathBuf>>(core_path: P) -> Self {
+        Self {
+            release_url: MIHOMO_RELEASE.to_string(),
+            mirror: String::new(),
+            with_proxy: false,
+            core_path: core_path.into(),
         }
-        // mihomo-linux-amd64-compatible-go120-v1.18.1.gz
-        format!("{s}-{tag_name}.gz")
-    };
-    let name = name.as_str();
-    let path = {
-        let path = std::path::Path::new(path.as_ref());
-        if path.is_dir() {
-            path.join(name)
+    }
+    fn url(&self, url: &str) -> String {
+        if self.mirror.is_empty() {
+            url.to_string()
         } else {
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, "not dir!"));
+            format!("{}/{url}", self.mirror.trim_end_matches('/'))
         }
-    };
+    }
+}
+
+impl ClashUtil {
+    /// Download the core built for this machine, verify it and put it at `core_path`
+    ///
+    /// `core_path` is replaced at once, or left as is on failure.
+    /// File capabilities like `cap_net_admin` are not carried over.
+    pub fn install_mihomo(&self, opts: &MihomoInstall) -> io::Result<Installed> {
+        let release = self.github_release(&opts.release_url, opts.with_proxy)?;
+        let asset = asset_names(&release.tag_name)
+            .into_iter()
+            .find_map(|name| release.assets.iter().find(|a| a.name == name))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!(
+                        "No build for {}-{} in {}",
+                        std::env::consts::OS,
+                        std::env::consts::ARCH,
+                        release.tag_name
+                    ),
+                )
+            })?;
+        let checksum =
+            self.published_checksum(&release, &asset.name, opts.with_proxy, |u| opts.url(u))?;
+        let gz = self.download_checked(
+            opts.url(asset.get_url()),
+            opts.with_proxy,
+            &asset.name,
+            &checksum,
+        )?;
+
+        let new = with_suffix(&opts.core_path, ".new");
+        let backup = unpack(&gz, &new)
+            .and_then(|_| check_runs(&new))
+            .and_then(|_| replace(&new, &opts.core_path))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&new);
+            })?;
+        Ok(Installed {
+            tag_name: release.tag_name,
+            asset: asset.name.clone(),
+            backup,
+        })
+    }
+
+    /// Download the `.gz` asset `name` of `release` and unpack it as an executable at `to`
+    ///
+    /// The asset is checked against the `checksums.txt` of `release`, nothing is written
+    /// without one. `to` is kept on failure.
+    pub fn download_gz(
+        &self,
+        release: &GithubApi,
+        name: &str,
+        with_proxy: bool,
+        to: &Path,
+    ) -> io::Result<()> {
+        let asset = release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No {name} in {}", release.tag_name)))?;
+        let checksum = self.published_checksum(release, name, with_proxy, str::to_string)?;
+        let gz = self.download_checked(asset.get_url().as_str(), with_proxy, name, &checksum)?;
+        let new = with_suffix(to, ".new");
+        unpack(&gz, &new)
+            .and_then(|_| fs::rename(&new, to))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&new);
+            })
+    }
+
+    /// The sha256 of `name` in the [CHECKSUMS] of `release`, `url` maps the download url
+    fn published_checksum<F>(
+        &self,
+        release: &GithubApi,
+        name: &str,
+        with_proxy: bool,
+        url: F,
+    ) -> io::Result<String>
+    where
+        F: Fn(&str) -> String,
+    {
+        let Some(sums) = release.assets.iter().find(|a| a.name == CHECKSUMS) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No {CHECKSUMS} in {}", release.tag_name),
+            ));
+        };
+        let mut buf = Vec::new();
+        self.download(url(sums.get_url()), with_proxy)?
+            .copy_to(&mut buf)?;
+        parse_checksum(&String::from_utf8_lossy(&buf), name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No checksum of {name} in {CHECKSUMS}"),
+            )
+        })
+    }
+
+    /// Download `name` from `url`, its sha256 must be `checksum`
+    fn download_checked<S: Into<minreq::URL>>(
+        &self,
+        url: S,
+        with_proxy: bool,
+        name: &str,
+        checksum: &str,
+    ) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.download(url, with_proxy)?.copy_to(&mut data)?;
+        let digest = sha256_hex(&data);
+        if !digest.eq_ignore_ascii_case(checksum) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Checksum mismatch of {name}: {digest}, expected {checksum}"),
+            ));
+        }
+        Ok(data)
+    }
+}
+
+/// Put back the binary replaced by the last [install_mihomo][ClashUtil::install_mihomo]
+pub fn rollback_mihomo<P: AsRef<Path>>(core_path: P) -> io::Result<()> {
+    let core_path = core_path.as_ref();
+    fs::rename(with_suffix(core_path, ".bak"), core_path)
+}
+
+/// Names of the assets that run here, the best first
+///
+/// e.g. `mihomo-linux-amd64-v3-v1.18.5.gz`
+fn asset_names(tag_name: &str) -> Vec<String> {
+    arch_names()
+        .iter()
+        .map(|arch| format!("mihomo-{}-{arch}-{tag_name}.gz", std::env::consts::OS))
+        .collect()
+}
+
+fn arch_names() -> &'static [&'static str] {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // The plain `amd64` build targets v3 while `compatible` is v1
+        match amd64_level() {
+            3 => &["amd64-v3", "amd64", "amd64-v2", "amd64-v1", "amd64-compatible"],
+            2 => &["amd64-v2", "amd64-v1", "amd64-compatible"],
+            _ => &["amd64-v1", "amd64-compatible"],
+        }
+    }
+    #[cfg(target_arch = "x86")]
+    {
+        &["386"]
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        &["arm64"]
+    }
+    #[cfg(all(target_arch = "arm", target_feature = "v7"))]
+    {
+        &["armv7", "armv6", "armv5"]
+    }
+    #[cfg(all(target_arch = "arm", not(target_feature = "v7")))]
+    {
+        &["armv6", "armv5"]
+    }
+    #[cfg(target_arch = "riscv64")]
+    {
+        &["riscv64"]
+    }
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "x86",
+        target_arch = "aarch64",
+        target_arch = "arm",
+        target_arch = "riscv64"
+    )))]
+    {
+        &[]
+    }
+}
 
-    if let Some(v) = apinfo.assets.iter().find(|s| s.name == name) {
-        let mut fp = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&path)?;
-        std::io::copy(&mut dl(&v.browser_download_url), &mut fp)?;
+/// The x86-64 microarchitecture level of the cpu, see GOAMD64
+#[cfg(target_arch = "x86_64")]
+fn amd64_level() -> u8 {
+    let v2 = is_x86_feature_detected!("cmpxchg16b")
+        && is_x86_feature_detected!("popcnt")
+        && is_x86_feature_detected!("sse3")
+        && is_x86_feature_detected!("ssse3")
+        && is_x86_feature_detected!("sse4.1")
+        && is_x86_feature_detected!("sse4.2");
+    let v3 = v2
+        && is_x86_feature_detected!("avx")
+        && is_x86_feature_detected!("avx2")
+        && is_x86_feature_detected!("bmi1")
+        && is_x86_feature_detected!("bmi2")
+        && is_x86_feature_detected!("f16c")
+        && is_x86_feature_detected!("fma")
+        && is_x86_feature_detected!("lzcnt")
+        && is_x86_feature_detected!("movbe")
+        && is_x86_feature_detected!("xsave");
+    match (v2, v3) {
+        (_, true) => 3,
+        (true, _) => 2,
+        _ => 1,
+    }
+}
+
+/// Find `name` in the output of `sha256sum`
+fn parse_checksum(sums: &str, name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        // `*` marks the binary mode
+        (file.trim_start().trim_start_matches('*') == name).then(|| hash.to_string())
+    })
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = OsString::from(path.as_os_str());
+    s.push(suffix);
+    s.into()
+}
+
+fn unpack(gz: &[u8], to: &Path) -> io::Result<()> {
+    let mut file = fs::File::create(to)?;
+    io::copy(&mut GzDecoder::new(gz), &mut file)?;
+    file.set_permissions(fs::Permissions::from_mode(0o755))?;
+    file.sync_all()
+}
+
+/// A build for another cpu or a broken download fails here, before anything is replaced
+fn check_runs(program: &Path) -> io::Result<()> {
+    let output = std::process::Command::new(program).arg("-v").output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The new core doesn't run: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ))
+    }
+}
+
+/// Move `new` to `core_path` in one step, returns the backup of the previous one
+fn replace(new: &Path, core_path: &Path) -> io::Result<Option<PathBuf>> {
+    let backup = if core_path.is_file() {
+        let backup = with_suffix(core_path, ".bak");
+        let _ = fs::remove_file(&backup);
+        // a hard link leaves `core_path` in place until the rename
+        if fs::hard_link(core_path, &backup).is_err() {
+            fs::copy(core_path, &backup)?;
+        }
+        Some(backup)
+    } else {
+        None
     };
-    Ok(path.join(name))
+    fs::rename(new, core_path)?;
+    Ok(backup)
 }
-#[test]
-fn doit() {
-    let cur = std::env::current_dir().unwrap();
-    println!("{cur:?}");
-    let worksapce = cur.parent().unwrap().parent().unwrap();
-    let program = dl_mihomo(worksapce).unwrap();
-    // TODO:unzip the file and chmod
-    std::process::Command::new(program)
-        .args([
-            "-d",
-            worksapce.join("Example").to_str().unwrap(),
-            "-f",
-            worksapce
-                .join("Example")
-                .join("basic_clash_config.yaml")
-                .to_str()
-                .unwrap(),
-        ])
-        .spawn()
-        .unwrap();
+
+#[cfg(test)]
+mod tests {
+    use super::{asset_names, parse_checksum, rollback_mihomo, sha256_hex, MihomoInstall};
+    use crate::{mock::MockController, ClashUtil};
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const TAG: &str = "v1.18.5";
+    // Stands in for the core, only `-v` is run
+    const FAKE_CORE: &str = "#!/bin/sh\necho Mihomo Meta v1.18.5\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    /// Publish a release like github, the assets are only found through `/mirror/`
+    fn publish(mock: &MockController, gz: &[u8], checksum: &str, dir: &str) -> MihomoInstall {
+        let name = asset_names(TAG).remove(0);
+        let github = "https://github.com/MetaCubeX/mihomo/releases/download";
+        let release = serde_json::json!({
+            "id": 1, "name": TAG, "tag_name": TAG,
+            "draft": false, "prerelease": false, "published_at": "2024-05-01T00:00:00Z",
+            "assets": [
+                {"name": "mihomo-other-arch.gz", "browser_download_url": format!("{github}/{TAG}/other.gz")},
+                {"name": name, "browser_download_url": format!("{github}/{TAG}/{name}")},
+                {"name": "checksums.txt", "browser_download_url": format!("{github}/{TAG}/checksums.txt")}
+            ]
+        });
+        mock.serve("/release", 200, &[], release.to_string());
+        mock.serve(&format!("/mirror/{github}/{TAG}/{name}"), 200, &[], gz);
+        let sums = format!("{}  mihomo-other-arch.gz\n{checksum} *{name}\n", "0".repeat(64));
+        mock.serve(&format!("/mirror/{github}/{TAG}/checksums.txt"), 200, &[], sums);
+
+        let dir = std::env::temp_dir().join(dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("mihomo"), "old").unwrap();
+        MihomoInstall {
+            release_url: format!("{}/release", mock.url()),
+            mirror: format!("{}/mirror/", mock.url()),
+            with_proxy: false,
+            core_path: dir.join("mihomo"),
+        }
+    }
+    fn sym(mock: &MockController) -> ClashUtil {
+        ClashUtil::new(mock.url(), String::new(), String::new(), String::new(), Default::default())
+    }
+
+    #[test]
+    fn install_test() {
+        let mock = MockController::start("");
+        let gz = gzip(FAKE_CORE.as_bytes());
+        let opts = publish(&mock, &gz, &sha256_hex(&gz), "clashtui_install");
+        let installed = sym(&mock).install_mihomo(&opts).unwrap();
+        assert_eq!(installed.tag_name, TAG);
+        assert_eq!(std::fs::read_to_string(&opts.core_path).unwrap(), FAKE_CORE);
+        let backup = installed.backup.unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "old");
+
+        rollback_mihomo(&opts.core_path).unwrap();
+        assert_eq!(std::fs::read_to_string(&opts.core_path).unwrap(), "old");
+        assert!(!backup.exists());
+        let _ = std::fs::remove_dir_all(opts.core_path.parent().unwrap());
+    }
+    #[test]
+    fn checksum_mismatch_test() {
+        let mock = MockController::start("");
+        let gz = gzip(FAKE_CORE.as_bytes());
+        let opts = publish(&mock, &gz, &"ab".repeat(32), "clashtui_install_mismatch");
+        let e = sym(&mock).install_mihomo(&opts).unwrap_err();
+        assert!(e.to_string().contains("Checksum mismatch"));
+        // left as is
+        assert_eq!(std::fs::read_to_string(&opts.core_path).unwrap(), "old");
+        let _ = std::fs::remove_dir_all(opts.core_path.parent().unwrap());
+    }
+    #[test]
+    fn download_gz_test() {
+        let mock = MockController::start("");
+        let gz = gzip(FAKE_CORE.as_bytes());
+        let name = "clashtui-linux-amd64-v0.2.2.gz";
+        mock.serve("/a.gz", 200, &[], gz.clone());
+        let asset = serde_json::json!({"name": name, "browser_download_url": format!("{}/a.gz", mock.url())});
+        let sums = serde_json::json!({"name": "checksums.txt", "browser_download_url": format!("{}/checksums.txt", mock.url())});
+        let release = |assets| -> crate::GithubApi {
+            serde_json::from_value(serde_json::json!({
+                "id": 1, "name": "v0.2.2", "tag_name": "v0.2.2",
+                "draft": false, "prerelease": false, "published_at": "2024-05-01T00:00:00Z",
+                "assets": assets
+            }))
+            .unwrap()
+        };
+        let to = std::env::temp_dir().join("clashtui_download_gz");
+        let _ = std::fs::remove_file(&to);
+
+        // nothing is written without a checksum
+        let no_sums = release(serde_json::json!([asset]));
+        assert!(sym(&mock).download_gz(&no_sums, name, false, &to).is_err());
+        assert!(!to.exists());
+        mock.serve("/checksums.txt", 200, &[], format!("{} *{name}\n", "ab".repeat(32)));
+        let with_sums = release(serde_json::json!([asset, sums]));
+        assert!(sym(&mock).download_gz(&with_sums, name, false, &to).is_err());
+        assert!(!to.exists());
+
+        mock.serve("/checksums.txt", 200, &[], format!("{} *{name}\n", sha256_hex(&gz)));
+        sym(&mock).download_gz(&with_sums, name, false, &to).unwrap();
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), FAKE_CORE);
+        let _ = std::fs::remove_file(&to);
+    }
+    #[test]
+    fn parse_checksum_test() {
+        let sums = "aa11  mihomo-linux-arm64-v1.gz\nbb22 *mihomo-linux-amd64-v1.gz\n";
+        assert_eq!(parse_checksum(sums, "mihomo-linux-amd64-v1.gz").as_deref(), Some("bb22"));
+        assert_eq!(parse_checksum(sums, "mihomo-linux-arm64-v1.gz").as_deref(), Some("aa11"));
+        assert!(parse_checksum(sums, "mihomo-linux-386-v1.gz").is_none());
+    }
 }
@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// Response of `GET /dns/query`, the json form of a dns message
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DnsQuery {
+    #[serde(rename = "Status")]
+    pub status: u16,
+    #[serde(rename = "Answer")]
+    pub answer: Vec<DnsAnswer>,
+}
+impl DnsQuery {
+    /// Name of the response code
+    pub fn status_str(&self) -> String {
+        match self.status {
+            0 => "NOERROR".to_string(),
+            1 => "FORMERR".to_string(),
+            2 => "SERVFAIL".to_string(),
+            3 => "NXDOMAIN".to_string(),
+            4 => "NOTIMP".to_string(),
+            5 => "REFUSED".to_string(),
+            n => format!("RCODE{n}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct DnsAnswer {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+impl DnsAnswer {
+    pub fn type_str(&self) -> String {
+        match self.record_type {
+            1 => "A".to_string(),
+            2 => "NS".to_string(),
+            5 => "CNAME".to_string(),
+            6 => "SOA".to_string(),
+            12 => "PTR".to_string(),
+            15 => "MX".to_string(),
+            16 => "TXT".to_string(),
+            28 => "AAAA".to_string(),
+            33 => "SRV".to_string(),
+            64 => "SVCB".to_string(),
+            65 => "HTTPS".to_string(),
+            n => format!("TYPE{n}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DnsQuery;
+    #[test]
+    fn parse_dns_query() {
+        let query: DnsQuery = serde_json::from_str(
+            r#"{"Status":0,"TC":false,"RD":true,"RA":true,"AD":false,"CD":false,"Question":[{"Name":"example.com.","Qtype":1,"Qclass":1}],"Answer":[{"name":"example.com.","type":1,"TTL":1,"data":"198.18.0.5"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(query.status_str(), "NOERROR");
+        assert_eq!(query.answer[0].type_str(), "A");
+        assert_eq!(query.answer[0].data, "198.18.0.5");
+        // no `Answer` for NXDOMAIN
+        let query: DnsQuery = serde_json::from_str(r#"{"Status":3}"#).unwrap();
+        assert_eq!(query.status_str(), "NXDOMAIN");
+        assert!(query.answer.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+/// Errors of [ClashUtil][crate::ClashUtil]
+#[derive(Debug)]
+pub enum ApiError {
+    /// Failed to send the request or to read the response
+    Request(minreq::Error),
+    /// The server answered with a non-2xx status
+    ///
+    /// `message` is the reason given by mihomo as `{"message": "..."}`, might be empty
+    Status {
+        code: i32,
+        reason: String,
+        message: String,
+    },
+    /// The response is not what we expect
+    Decode(serde_json::Error),
+    /// Nothing is sent for bad arguments
+    InvalidInput(&'static str),
+}
+pub type Result<T> = core::result::Result<T, ApiError>;
+
+impl ApiError {
+    pub(crate) fn from_status(code: i32, reason: &str, body: &str) -> Self {
+        let message = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|v| v.get("message").and_then(|m| m.as_str()).map(|m| m.to_owned()))
+            .unwrap_or_else(|| body.trim().to_owned());
+        Self::Status {
+            code,
+            reason: reason.to_owned(),
+            message,
+        }
+    }
+    pub fn status_code(&self) -> Option<i32> {
+        match self {
+            Self::Status { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+    /// e.g. the endpoint is missing in an old core
+    pub fn is_not_found(&self) -> bool {
+        self.status_code() == Some(404)
+    }
+    /// The secret is wrong or missing
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self.status_code(), Some(401 | 403))
+    }
+    /// Nothing was read in time, a [stream][crate::JsonStream] can still be read on
+    pub fn is_timeout(&self) -> bool {
+        use std::io::ErrorKind;
+        matches!(
+            self,
+            Self::Request(minreq::Error::IoError(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+        )
+    }
+    /// Might succeed if sent again, e.g. a timeout or a 5xx
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(minreq::Error::IoError(_) | minreq::Error::AddressNotFound) => true,
+            Self::Status { code, .. } => *code >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "{e}"),
+            Self::Status {
+                code,
+                reason,
+                message,
+            } if message.is_empty() => write!(f, "{code} {reason}"),
+            Self::Status {
+                code,
+                reason,
+                message,
+            } => write!(f, "{code} {reason}: {message}"),
+            Self::Decode(e) => write!(f, "Invalid response: {e}"),
+            Self::InvalidInput(s) => write!(f, "{s}"),
+        }
+    }
+}
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request(e) => Some(e),
+            Self::Decode(e) => Some(e),
+            Self::Status { .. } | Self::InvalidInput(_) => None,
+        }
+    }
+}
+
+impl From<minreq::Error> for ApiError {
+    fn from(e: minreq::Error) -> Self {
+        Self::Request(e)
+    }
+}
+/// Transport errors of [unix socket][crate::ClashUtil::new_unix]
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Request(minreq::Error::IoError(e))
+    }
+}
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Decode(e)
+    }
+}
+
+/// For callers working with [std::io::Error]
+impl From<ApiError> for std::io::Error {
+    fn from(e: ApiError) -> Self {
+        use std::io::{Error, ErrorKind};
+        match e {
+            ApiError::Request(e) => process_err(e),
+            ApiError::Status { code, .. } => {
+                let kind = match code {
+                    404 => ErrorKind::NotFound,
+                    401 | 403 => ErrorKind::PermissionDenied,
+                    _ => ErrorKind::Other,
+                };
+                Error::new(kind, e)
+            }
+            ApiError::Decode(_) => Error::new(ErrorKind::InvalidData, e),
+            ApiError::InvalidInput(_) => Error::new(ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+fn process_err(e: minreq::Error) -> std::io::Error {
+    use std::io::{Error, ErrorKind};
+    match e {
+        minreq::Error::AddressNotFound | minreq::Error::PunycodeConversionFailed => {
+            Error::new(ErrorKind::AddrNotAvailable, e)
+        }
+        minreq::Error::IoError(e) => e,
+        minreq::Error::HeadersOverflow
+        | minreq::Error::StatusLineOverflow
+        | minreq::Error::InvalidUtf8InBody(_)
+        | minreq::Error::InvalidUtf8InResponse
+        | minreq::Error::MalformedChunkLength
+        | minreq::Error::MalformedChunkEnd
+        | minreq::Error::MalformedContentLength => Error::new(ErrorKind::InvalidData, e),
+        minreq::Error::RedirectLocationMissing
+        | minreq::Error::InfiniteRedirectionLoop
+        | minreq::Error::TooManyRedirections => Error::new(ErrorKind::ConnectionAborted, e),
+        minreq::Error::HttpsFeatureNotEnabled => unreachable!("https should already be enabled"),
+        minreq::Error::PunycodeFeatureNotEnabled => panic!("{}", e),
+        minreq::Error::RustlsCreateConnection(_) => Error::new(ErrorKind::ConnectionRefused, e),
+        minreq::Error::BadProxy
+        | minreq::Error::BadProxyCreds
+        | minreq::Error::ProxyConnect
+        | minreq::Error::InvalidProxyCreds => Error::new(ErrorKind::PermissionDenied, e),
+        minreq::Error::Other(i) => Error::other(i),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApiError;
+    #[test]
+    fn status_error_test() {
+        let e = ApiError::from_status(404, "Not Found", r#"{"message":"Resource not found"}"#);
+        assert!(e.is_not_found());
+        assert_eq!(e.to_string(), "404 Not Found: Resource not found");
+        let e = ApiError::from_status(401, "Unauthorized", "");
+        assert!(e.is_unauthorized());
+        assert_eq!(e.to_string(), "401 Unauthorized");
+        let e: std::io::Error = e.into();
+        assert_eq!(e.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+}
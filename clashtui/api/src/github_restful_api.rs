@@ -11,6 +11,11 @@ pub struct GithubApi {
 
     pub published_at: String,
     pub assets: Vec<Asset>,
+    // The release notes in markdown
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub html_url: String,
 }
 impl GithubApi {
     pub fn check(&self, current_version: &str) -> bool {
@@ -0,0 +1,175 @@
+//! A minimal HTTP/1.1 client for what minreq can't reach, see [unix][crate::unix] and [tls][crate::tls],
+//! and for streams over plain http, which need a timeout per read
+//!
+//! This covers what the controller needs:
+//! one request per connection, with a body of fixed length or chunked.
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+pub(crate) struct Response {
+    pub status_code: i32,
+    pub reason_phrase: String,
+    pub body: Box<dyn BufRead + Send>,
+}
+impl Response {
+    pub fn into_string(mut self) -> Result<String> {
+        let mut s = String::new();
+        self.body.read_to_string(&mut s)?;
+        Ok(s)
+    }
+}
+
+/// Plain http to `addr`, e.g. `127.0.0.1:9090`
+///
+/// `timeout` applies to every read and write, `None` waits forever
+pub(crate) fn send(
+    addr: &str,
+    method: &str,
+    sub_url: &str,
+    secret: &str,
+    body: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<Response> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    exchange(stream, addr, method, sub_url, secret, body)
+}
+
+/// Send one request on a fresh connection
+pub(crate) fn exchange<S: Read + Write + Send + 'static>(
+    mut stream: S,
+    host: &str,
+    method: &str,
+    sub_url: &str,
+    secret: &str,
+    body: Option<&str>,
+) -> Result<Response> {
+    let body = body.unwrap_or_default();
+    let mut head = format!(
+        "{method} {sub_url} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    if !secret.is_empty() {
+        head.push_str(&format!("Authorization: Bearer {secret}\r\n"));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // e.g. `HTTP/1.1 200 OK`
+    let mut parts = line.trim_end().splitn(3, ' ');
+    let status_code = parts
+        .nth(1)
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(|| invalid("Bad status line"))?;
+    let reason_phrase = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("Unexpected end of headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((k, v)) = header.split_once(':') else {
+            continue;
+        };
+        let v = v.trim();
+        if k.eq_ignore_ascii_case("content-length") {
+            content_length = Some(v.parse::<u64>().map_err(|_| invalid("Bad Content-Length"))?);
+        } else if k.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = v.eq_ignore_ascii_case("chunked");
+        }
+    }
+
+    let body: Box<dyn BufRead + Send> = if chunked {
+        Box::new(BufReader::new(Chunked {
+            inner: reader,
+            left: 0,
+            done: false,
+        }))
+    } else if let Some(len) = content_length {
+        Box::new(reader.take(len))
+    } else {
+        // until the server closes the connection
+        Box::new(reader)
+    };
+    Ok(Response {
+        status_code,
+        reason_phrase,
+        body,
+    })
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Decode `Transfer-Encoding: chunked`, reads return as soon as some data arrives
+struct Chunked<R> {
+    inner: R,
+    // bytes left in the current chunk
+    left: u64,
+    done: bool,
+}
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.left == 0 {
+            let mut line = String::new();
+            if self.inner.read_line(&mut line)? == 0 {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+            // extensions after `;` are ignored
+            let size = line.trim().split(';').next().unwrap_or_default();
+            self.left = u64::from_str_radix(size, 16).map_err(|_| invalid("Bad chunk size"))?;
+            if self.left == 0 {
+                // trailers are ignored
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let max = buf.len().min(self.left as usize);
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        self.left -= n as u64;
+        if self.left == 0 {
+            let mut crlf = String::new();
+            self.inner.read_line(&mut crlf)?;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Chunked;
+    use std::io::Read;
+    #[test]
+    fn chunked_test() {
+        let raw = b"5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n";
+        let mut body = String::new();
+        Chunked {
+            inner: &raw[..],
+            left: 0,
+            done: false,
+        }
+        .read_to_string(&mut body)
+        .unwrap();
+        assert_eq!(body, "hello, world");
+    }
+}
@@ -1,11 +1,41 @@
 mod clash;
 mod config;
-#[cfg(target_feature = "deprecated")]
+mod connection;
+mod dns;
+mod error;
+mod http1;
+mod net;
+mod provider;
+mod proxy;
+mod rule;
+mod stream;
+mod subscription;
+mod tls;
+mod unix;
+mod utils;
+mod version;
+#[cfg(feature = "installer")]
 mod dl_mihomo;
-#[cfg(target_feature = "github_api")]
+#[cfg(feature = "github_api")]
 mod github_restful_api;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 
-pub use clash::{ClashUtil, Resp, ProfileSectionType};
-pub use config::{ClashConfig, Mode, TunStack};
-#[cfg(target_feature = "github_api")]
+pub use clash::{ClashUtil, JsonStream, Resp, ProfileSectionType};
+pub use config::{ClashConfig, LogLevel, Mode, TunStack};
+pub use connection::{ClashConnection, ClashConnections, ConnMetadata};
+pub use dns::{DnsAnswer, DnsQuery};
+pub use error::{ApiError, Result};
+pub use net::NetConfig;
+pub use provider::{ClashProvider, ClashProviders};
+pub use proxy::{ClashProxies, ClashProxy, DelayHistory, ProxyDelay};
+pub use rule::{ClashRule, ClashRules};
+pub use stream::{ClashLog, Memory, Traffic};
+pub use subscription::SubscriptionInfo;
+pub use tls::TlsTrust;
+pub use utils::base64_decode;
+pub use version::ClashVersion;
+#[cfg(feature = "installer")]
+pub use dl_mihomo::{rollback_mihomo, Installed, MihomoInstall, MIHOMO_RELEASE};
+#[cfg(feature = "github_api")]
 pub use github_restful_api::GithubApi;
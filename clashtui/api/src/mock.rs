@@ -0,0 +1,639 @@
+//! A fake mihomo controller for offline tests
+//!
+//! It speaks the part of the REST api used by clashtui, checks the Bearer
+//! secret, keeps the config mutable and serves fixed proxies, providers and rules.
+//! Extra paths can be registered with [MockController::serve], e.g. a subscription.
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub const MOCK_VERSION: &str = "v1.18.0-mock";
+/// CA of the certificate used by [MockController::start_tls]
+pub const MOCK_CA_PEM: &str = include_str!("../testdata/controller_ca.pem");
+/// SHA-256 of the certificate used by [MockController::start_tls], valid for `127.0.0.1` and `localhost`
+pub const MOCK_CERT_SHA256: &str =
+    "AD:CA:C6:46:EE:29:7D:40:95:BC:0C:18:7B:FC:9C:C3:90:B6:C8:2F:5F:E6:5A:A4:5D:C1:BE:E7:E5:6A:C2:3B";
+const MOCK_CERT_PEM: &str = include_str!("../testdata/controller_cert.pem");
+const MOCK_KEY_PEM: &str = include_str!("../testdata/controller_key.pem");
+
+#[derive(Clone)]
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    // send the lines of `body` with chunked encoding, like `/traffic`
+    chunked: bool,
+}
+impl Reply {
+    fn json(status: u16, v: Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: v.to_string().into_bytes(),
+            chunked: false,
+        }
+    }
+    fn no_content() -> Self {
+        Self {
+            status: 204,
+            headers: vec![],
+            body: vec![],
+            chunked: false,
+        }
+    }
+    fn message(status: u16, msg: &str) -> Self {
+        Self::json(status, json!({ "message": msg }))
+    }
+    fn stream(lines: &[Value]) -> Self {
+        let body: String = lines.iter().map(|l| format!("{l}\n")).collect();
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.into_bytes(),
+            chunked: true,
+        }
+    }
+}
+
+struct State {
+    config: Value,
+    proxies: Value,
+    connections: Vec<Value>,
+    files: HashMap<String, Reply>,
+    // `METHOD path` of every request, in order
+    hits: Vec<String>,
+    // TUN can't be enabled, like a core without `cap_net_admin`
+    tun_denied: bool,
+    // latency of served files, and how many were served at once
+    file_delay: std::time::Duration,
+    files_in_flight: usize,
+    max_files_in_flight: usize,
+    // how long streams stay open after the last line
+    stream_idle: std::time::Duration,
+}
+
+enum Listen {
+    Tcp(std::net::SocketAddr),
+    Tls(std::net::SocketAddr),
+    Unix(PathBuf),
+}
+
+pub struct MockController {
+    listen: Listen,
+    state: Arc<Mutex<State>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MockController {
+    /// Listen on a random local port, an empty `secret` disables the check
+    pub fn start(secret: &str) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock controller");
+        let addr = listener.local_addr().unwrap();
+        let mock = Self::new(Listen::Tcp(addr));
+        let incoming = std::iter::from_fn(move || Some(listener.accept().map(|(s, _)| s)));
+        mock.accept(incoming, secret);
+        mock
+    }
+    /// Like [start][Self::start] but over https, like `external-controller-tls`
+    ///
+    /// The certificate is signed by [MOCK_CA_PEM]
+    pub fn start_tls(secret: &str) -> Self {
+        use crate::tls::pem_blocks;
+        let certs = pem_blocks(MOCK_CERT_PEM, "CERTIFICATE")
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+        let key = rustls::PrivateKey(pem_blocks(MOCK_KEY_PEM, "PRIVATE KEY").remove(0));
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .expect("mock certificate");
+        let config = Arc::new(config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock controller");
+        let addr = listener.local_addr().unwrap();
+        let mock = Self::new(Listen::Tls(addr));
+        let incoming = std::iter::from_fn(move || {
+            Some(listener.accept().and_then(|(s, _)| {
+                let conn =
+                    rustls::ServerConnection::new(config.clone()).map_err(std::io::Error::other)?;
+                Ok(rustls::StreamOwned::new(conn, s))
+            }))
+        });
+        mock.accept(incoming, secret);
+        mock
+    }
+    /// Listen on `socket` like `external-controller-unix`, which is removed once dropped
+    pub fn start_unix(socket: PathBuf, secret: &str) -> Self {
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).expect("bind mock controller");
+        let mock = Self::new(Listen::Unix(socket));
+        let incoming = std::iter::from_fn(move || Some(listener.accept().map(|(s, _)| s)));
+        mock.accept(incoming, secret);
+        mock
+    }
+    fn new(listen: Listen) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            config: default_config(),
+            proxies: default_proxies(),
+            connections: vec![default_connection()],
+            files: HashMap::new(),
+            hits: vec![],
+            tun_denied: false,
+            file_delay: std::time::Duration::ZERO,
+            files_in_flight: 0,
+            max_files_in_flight: 0,
+            stream_idle: std::time::Duration::ZERO,
+        }));
+        Self {
+            listen,
+            state,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    fn accept<I, S>(&self, incoming: I, secret: &str)
+    where
+        I: Iterator<Item = std::io::Result<S>> + Send + 'static,
+        S: Read + Write + Send + 'static,
+    {
+        let state = self.state.clone();
+        let stop = self.stop.clone();
+        let secret = secret.to_string();
+        std::thread::spawn(move || {
+            for stream in incoming {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let state = state.clone();
+                let secret = secret.clone();
+                std::thread::spawn(move || handle(stream, &state, &secret));
+            }
+        });
+    }
+    /// Like `http://127.0.0.1:{port}`, or `https://` after [start_tls][Self::start_tls]
+    pub fn url(&self) -> String {
+        match &self.listen {
+            Listen::Tls(_) => format!("https://{}", self.addr()),
+            _ => format!("http://{}", self.addr()),
+        }
+    }
+    /// Like `127.0.0.1:{port}`, the form of `external-controller`
+    pub fn addr(&self) -> String {
+        match &self.listen {
+            Listen::Tcp(addr) | Listen::Tls(addr) => addr.to_string(),
+            Listen::Unix(_) => panic!("Not listening on tcp"),
+        }
+    }
+    /// Serve `body` at `path` without auth, for files like subscriptions
+    pub fn serve(&self, path: &str, status: u16, headers: &[(&str, &str)], body: impl Into<Vec<u8>>) {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let reply = Reply {
+            status,
+            headers,
+            body: body.into(),
+            chunked: false,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .insert(path.to_string(), reply);
+    }
+    /// The running config, as changed by `PATCH /configs`
+    pub fn config(&self) -> Value {
+        self.state.lock().unwrap().config.clone()
+    }
+    /// Fail to enable TUN from now on, like a core without `cap_net_admin`
+    pub fn deny_tun(&self) {
+        self.state.lock().unwrap().tun_denied = true;
+    }
+    /// Keep streams open for `idle` after the last line, like a quiet `/logs`
+    pub fn idle_streams(&self, idle: std::time::Duration) {
+        self.state.lock().unwrap().stream_idle = idle;
+    }
+    /// Take `delay` to serve each file, like a slow subscription
+    pub fn delay_files(&self, delay: std::time::Duration) {
+        self.state.lock().unwrap().file_delay = delay;
+    }
+    /// The most files served at once so far
+    pub fn max_files_in_flight(&self) -> usize {
+        self.state.lock().unwrap().max_files_in_flight
+    }
+    /// Selected member of a group
+    pub fn selected(&self, group: &str) -> Option<String> {
+        self.state.lock().unwrap().proxies[group]["now"]
+            .as_str()
+            .map(|s| s.to_string())
+    }
+    /// `METHOD path` of every request received so far
+    pub fn hits(&self) -> Vec<String> {
+        self.state.lock().unwrap().hits.clone()
+    }
+}
+impl Drop for MockController {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // wake up the listener
+        match &self.listen {
+            Listen::Tcp(addr) | Listen::Tls(addr) => {
+                let _ = TcpStream::connect(addr);
+            }
+            Listen::Unix(socket) => {
+                let _ = UnixStream::connect(socket);
+                let _ = std::fs::remove_file(socket);
+            }
+        }
+    }
+}
+
+fn handle<S: Read + Write>(mut stream: S, state: &Mutex<State>, secret: &str) {
+    let mut reader = BufReader::new(&mut stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return;
+    };
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            headers.insert(k.trim().to_lowercase(), v.trim().to_string());
+        }
+    }
+    let len: usize = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; len];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    // minreq tunnels any request through a proxy, the mock stands in for `mixed-port` too
+    if method == "CONNECT" {
+        drop(reader);
+        if stream
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .is_err()
+        {
+            return;
+        }
+        return handle(stream, state, secret);
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query: HashMap<String, String> = query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (k.to_string(), decode(v)))
+        .collect();
+    let mut is_file = false;
+    let reply = {
+        let mut state = state.lock().unwrap();
+        state.hits.push(format!("{method} {path}"));
+        if let Some(reply) = state.files.get(path).cloned() {
+            is_file = true;
+            state.files_in_flight += 1;
+            state.max_files_in_flight = state.max_files_in_flight.max(state.files_in_flight);
+            reply
+        } else if !secret.is_empty()
+            && headers.get("authorization") != Some(&format!("Bearer {secret}"))
+        {
+            Reply::message(401, "Unauthorized")
+        } else {
+            let segs: Vec<String> = path.split('/').skip(1).map(decode).collect();
+            let segs: Vec<&str> = segs.iter().map(|s| s.as_str()).collect();
+            route(&mut state, method, &segs, &query, &body)
+        }
+    };
+    if is_file {
+        let delay = state.lock().unwrap().file_delay;
+        std::thread::sleep(delay);
+    }
+    let idle = state.lock().unwrap().stream_idle;
+    let _ = write_reply(&mut stream, reply, idle);
+    if is_file {
+        state.lock().unwrap().files_in_flight -= 1;
+    }
+}
+
+/// `idle` is waited before a chunked reply ends
+fn write_reply<S: Write>(stream: &mut S, reply: Reply, idle: std::time::Duration) -> std::io::Result<()> {
+    write!(stream, "HTTP/1.1 {} {}\r\n", reply.status, reason(reply.status))?;
+    for (k, v) in reply.headers.iter() {
+        write!(stream, "{k}: {v}\r\n")?;
+    }
+    write!(stream, "Connection: close\r\n")?;
+    if reply.chunked {
+        write!(stream, "Transfer-Encoding: chunked\r\n\r\n")?;
+        for line in reply.body.split_inclusive(|b| *b == b'\n') {
+            write!(stream, "{:x}\r\n", line.len())?;
+            stream.write_all(line)?;
+            write!(stream, "\r\n")?;
+        }
+        stream.flush()?;
+        std::thread::sleep(idle);
+        write!(stream, "0\r\n\r\n")?;
+    } else {
+        write!(stream, "Content-Length: {}\r\n\r\n", reply.body.len())?;
+        stream.write_all(&reply.body)?;
+    }
+    stream.flush()
+}
+
+fn route(
+    state: &mut State,
+    method: &str,
+    segs: &[&str],
+    query: &HashMap<String, String>,
+    body: &[u8],
+) -> Reply {
+    match (method, segs) {
+        ("GET", ["version"]) => Reply::json(200, json!({ "meta": true, "version": MOCK_VERSION })),
+        ("GET", ["configs"]) => Reply::json(200, state.config.clone()),
+        ("PATCH", ["configs"]) => match serde_json::from_slice::<Value>(body) {
+            Ok(mut patch) if patch.is_object() => {
+                // the core accepts any case
+                if let Some(mode) = patch["mode"].as_str() {
+                    patch["mode"] = json!(mode.to_lowercase());
+                }
+                if let Some(stack) = patch["tun"]["stack"].as_str() {
+                    let stack = match stack.to_lowercase().as_str() {
+                        "gvisor" => "gVisor",
+                        "system" => "System",
+                        _ => "Mixed",
+                    };
+                    patch["tun"]["stack"] = json!(stack);
+                }
+                merge(&mut state.config, patch);
+                // the core still answers 204 and only logs the failure
+                if state.tun_denied {
+                    state.config["tun"]["enable"] = json!(false);
+                }
+                Reply::no_content()
+            }
+            _ => Reply::message(400, "Body invalid"),
+        },
+        ("PUT", ["configs"]) => Reply::no_content(),
+        ("POST", ["configs", "geo"]) => Reply::no_content(),
+        ("POST", ["restart"]) => Reply::json(200, json!({ "status": "ok" })),
+        ("POST", ["cache", "fakeip", "flush"]) | ("POST", ["cache", "dns", "flush"]) => {
+            Reply::no_content()
+        }
+
+        ("GET", ["proxies"]) => Reply::json(200, json!({ "proxies": state.proxies })),
+        ("PUT", ["proxies", group]) => {
+            let name = serde_json::from_slice::<Value>(body)
+                .ok()
+                .and_then(|v| v["name"].as_str().map(|s| s.to_string()));
+            let Some(g) = state.proxies.get_mut(*group) else {
+                return Reply::message(404, "Resource not found");
+            };
+            match name {
+                Some(name) if g["all"].as_array().is_some_and(|a| a.contains(&json!(name))) => {
+                    g["now"] = json!(name);
+                    Reply::no_content()
+                }
+                _ => Reply::message(400, "Selector update error: proxy not exist"),
+            }
+        }
+        ("GET", ["proxies", name, "delay"]) => match state.proxies.get(*name) {
+            Some(_) if *name == "REJECT" => Reply::message(504, "Timeout"),
+            Some(_) => Reply::json(200, json!({ "delay": 42 })),
+            None => Reply::message(404, "Resource not found"),
+        },
+        ("GET", ["group", name, "delay"]) => match state.proxies[*name]["all"].as_array() {
+            Some(all) => {
+                let delays: serde_json::Map<String, Value> = all
+                    .iter()
+                    .filter_map(|m| m.as_str())
+                    // failed ones are absent
+                    .filter(|m| *m != "REJECT")
+                    .map(|m| (m.to_string(), json!(42)))
+                    .collect();
+                Reply::json(200, Value::Object(delays))
+            }
+            None => Reply::message(404, "Resource not found"),
+        },
+
+        ("GET", ["providers", "proxies"]) => {
+            let nodes = json!([state.proxies["node-a"], state.proxies["node-b"]]);
+            Reply::json(
+                200,
+                json!({ "providers": {
+                    "default": {
+                        "name": "default", "type": "Proxy", "vehicleType": "Compatible",
+                        "proxies": [], "updatedAt": "0001-01-01T00:00:00Z",
+                    },
+                    "sub": {
+                        "name": "sub", "type": "Proxy", "vehicleType": "HTTP",
+                        "proxies": nodes, "updatedAt": "2024-05-01T08:00:00.000000000+08:00",
+                    },
+                }}),
+            )
+        }
+        ("GET", ["providers", "rules"]) => Reply::json(
+            200,
+            json!({ "providers": {
+                "reject": {
+                    "name": "reject", "type": "Rule", "vehicleType": "HTTP",
+                    "behavior": "Domain", "ruleCount": 3,
+                    "updatedAt": "2024-05-01T08:00:00.000000000+08:00",
+                },
+            }}),
+        ),
+        ("PUT", ["providers", "proxies", "sub"])
+        | ("GET", ["providers", "proxies", "sub", "healthcheck"])
+        | ("PUT", ["providers", "rules", "reject"]) => Reply::no_content(),
+        ("PUT", ["providers", ..]) | ("GET", ["providers", ..]) => {
+            Reply::message(404, "Resource not found")
+        }
+
+        ("GET", ["rules"]) => Reply::json(
+            200,
+            json!({ "rules": [
+                { "type": "RuleSet", "payload": "reject", "proxy": "REJECT", "size": 3 },
+                { "type": "DomainSuffix", "payload": "example.com", "proxy": "Proxy", "size": -1 },
+                { "type": "Match", "payload": "", "proxy": "DIRECT", "size": -1 },
+            ]}),
+        ),
+        ("GET", ["connections"]) => Reply::json(
+            200,
+            json!({
+                "downloadTotal": 2048, "uploadTotal": 1024,
+                // the core sends `null` rather than `[]`
+                "connections": if state.connections.is_empty() {
+                    Value::Null
+                } else {
+                    json!(state.connections)
+                },
+            }),
+        ),
+        ("DELETE", ["connections"]) => {
+            state.connections.clear();
+            Reply::no_content()
+        }
+        ("DELETE", ["connections", id]) => {
+            state.connections.retain(|c| c["id"] != json!(id));
+            Reply::no_content()
+        }
+
+        ("GET", ["dns", "query"]) => {
+            let name = query.get("name").cloned().unwrap_or_default();
+            Reply::json(
+                200,
+                json!({
+                    "Status": 0,
+                    "Answer": [{ "name": format!("{name}."), "type": 1, "TTL": 600, "data": "198.18.0.1" }],
+                }),
+            )
+        }
+
+        ("GET", ["traffic"]) => Reply::stream(&[
+            json!({ "up": 1, "down": 2 }),
+            json!({ "up": 3, "down": 4 }),
+        ]),
+        ("GET", ["memory"]) => Reply::stream(&[json!({ "inuse": 1024, "oslimit": 0 })]),
+        ("GET", ["logs"]) => Reply::stream(&[json!({ "type": "info", "payload": "mock log" })]),
+
+        _ => Reply {
+            status: 404,
+            headers: vec![],
+            body: b"404 page not found".to_vec(),
+            chunked: false,
+        },
+    }
+}
+
+/// Objects are merged key by key, the others are replaced
+fn merge(dst: &mut Value, src: Value) {
+    match (dst, src) {
+        (Value::Object(dst), Value::Object(src)) => {
+            for (k, v) in src {
+                merge(dst.entry(k).or_insert(Value::Null), v);
+            }
+        }
+        (dst, src) => *dst = src,
+    }
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => out.push(b' '),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        504 => "Gateway Timeout",
+        _ => "Unknown",
+    }
+}
+
+fn default_config() -> Value {
+    json!({
+        "port": 0,
+        "socks-port": 0,
+        "redir-port": 0,
+        "tproxy-port": 0,
+        "mixed-port": 7890,
+        "allow-lan": false,
+        "bind-address": "*",
+        "authentication": null,
+        "lan-allowed-ips": ["0.0.0.0/0", "::/0"],
+        "lan-disallowed-ips": null,
+        "mode": "rule",
+        "log-level": "info",
+        "ipv6": false,
+        "interface-name": "",
+        "sniffing": false,
+        "tcp-concurrent": false,
+        "find-process-mode": "strict",
+        "tun": {
+            "enable": false,
+            "device": "",
+            "stack": "Mixed",
+            "auto-route": true,
+        },
+    })
+}
+
+fn default_proxies() -> Value {
+    json!({
+        "DIRECT": { "name": "DIRECT", "type": "Direct", "history": [], "alive": true, "udp": true },
+        "REJECT": { "name": "REJECT", "type": "Reject", "history": [], "alive": true, "udp": true },
+        "node-a": {
+            "name": "node-a", "type": "Shadowsocks", "alive": true, "udp": true,
+            "history": [{ "time": "2024-05-01T08:00:00.000000000+08:00", "delay": 42 }],
+        },
+        "node-b": { "name": "node-b", "type": "Vmess", "history": [], "alive": true, "udp": false },
+        "Proxy": {
+            "name": "Proxy", "type": "Selector", "now": "node-a",
+            "all": ["node-a", "node-b", "DIRECT"], "history": [], "alive": true, "udp": true,
+        },
+        "GLOBAL": {
+            "name": "GLOBAL", "type": "Selector", "now": "DIRECT",
+            "all": ["DIRECT", "REJECT", "Proxy"], "history": [], "alive": true, "udp": true,
+        },
+    })
+}
+
+fn default_connection() -> Value {
+    json!({
+        "id": "5b7d5f3a-0000-4000-8000-000000000001",
+        "metadata": {
+            "network": "tcp", "type": "HTTP",
+            "sourceIP": "127.0.0.1", "destinationIP": "93.184.216.34",
+            "sourcePort": "50000", "destinationPort": "443",
+            "host": "example.com", "process": "curl", "processPath": "/usr/bin/curl",
+        },
+        "upload": 1024, "download": 2048,
+        "start": "2024-05-01T08:00:00.000000000+08:00",
+        "chains": ["node-a", "Proxy"],
+        "rule": "DomainSuffix", "rulePayload": "example.com",
+    })
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Timeouts and retries of [ClashUtil][crate::ClashUtil]
+///
+/// Controller calls are local and should fail fast, while subscriptions
+/// and geo data might come from slow mirrors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetConfig {
+    /// Seconds to wait for the controller
+    pub controller_timeout: u64,
+    /// Extra attempts of a failed controller query, only `GET`s are retried
+    pub controller_retries: u32,
+    /// Seconds to wait for a subscription or another remote file
+    pub download_timeout: u64,
+    /// Extra attempts of a failed download
+    pub download_retries: u32,
+    /// Milliseconds to wait before the first retry, doubled after each one
+    pub retry_backoff: u64,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            controller_timeout: 10,
+            controller_retries: 0,
+            download_timeout: 120,
+            download_retries: 2,
+            retry_backoff: 500,
+        }
+    }
+}
+
+impl NetConfig {
+    /// Wait before the retry number `attempt`, counting from 0
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_millis(self.retry_backoff.saturating_mul(1 << attempt.min(16)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetConfig;
+    #[test]
+    fn backoff_test() {
+        let net = NetConfig {
+            retry_backoff: 100,
+            ..Default::default()
+        };
+        let waits: Vec<u128> = (0..4).map(|i| net.backoff(i).as_millis()).collect();
+        assert_eq!(waits, [100, 200, 400, 800]);
+        // missing keys keep their defaults
+        let net: NetConfig = serde_json::from_str(r#"{"download_timeout": 30}"#).unwrap();
+        assert_eq!(net.download_timeout, 30);
+        assert_eq!(
+            net.controller_timeout,
+            NetConfig::default().controller_timeout
+        );
+    }
+}
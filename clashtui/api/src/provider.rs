@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::ClashProxy;
+
+/// Response of `GET /providers/proxies` and `GET /providers/rules`
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ClashProviders {
+    pub providers: HashMap<String, ClashProvider>,
+}
+
+/// A proxy provider or a rule provider
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct ClashProvider {
+    pub name: String,
+    /// `Proxy` or `Rule`
+    #[serde(rename = "type")]
+    pub provider_type: String,
+    /// `HTTP`, `File`, `Inline`, or `Compatible` for the proxies not in any provider
+    pub vehicle_type: String,
+    /// Proxy providers only
+    pub proxies: Vec<ClashProxy>,
+    /// Rule providers only
+    pub behavior: String,
+    /// Rule providers only
+    pub rule_count: usize,
+    pub updated_at: String,
+}
+impl ClashProvider {
+    pub fn is_rule(&self) -> bool {
+        self.provider_type == "Rule"
+    }
+    /// Number of nodes or rules
+    pub fn count(&self) -> usize {
+        if self.is_rule() {
+            self.rule_count
+        } else {
+            self.proxies.len()
+        }
+    }
+    /// `None` if it's never updated
+    pub fn updated_at(&self) -> Option<std::time::SystemTime> {
+        chrono::DateTime::parse_from_rfc3339(&self.updated_at)
+            .ok()
+            // the core reports the zero time of go
+            .filter(|t| chrono::Datelike::year(t) > 1)
+            .map(|t| t.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClashProviders;
+    #[test]
+    fn parse_providers() {
+        let proxies: ClashProviders = serde_json::from_str(
+            r#"{"providers":{"sub":{"name":"sub","type":"Proxy","vehicleType":"HTTP","proxies":[{"name":"node-a","type":"Shadowsocks"}],"updatedAt":"2024-05-01T10:00:00.123+08:00"},"default":{"name":"default","type":"Proxy","vehicleType":"Compatible","proxies":[],"updatedAt":"0001-01-01T00:00:00Z"}}}"#,
+        )
+        .unwrap();
+        let sub = &proxies.providers["sub"];
+        assert_eq!(sub.count(), 1);
+        assert!(sub.updated_at().is_some());
+        assert!(proxies.providers["default"].updated_at().is_none());
+
+        let rules: ClashProviders = serde_json::from_str(
+            r#"{"providers":{"cn":{"name":"cn","type":"Rule","vehicleType":"HTTP","behavior":"domain","ruleCount":42,"updatedAt":"2024-05-01T10:00:00Z"}}}"#,
+        )
+        .unwrap();
+        assert!(rules.providers["cn"].is_rule());
+        assert_eq!(rules.providers["cn"].count(), 42);
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const GLOBAL: &str = "GLOBAL";
+
+/// Response of `GET /proxies`
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ClashProxies {
+    pub proxies: HashMap<String, ClashProxy>,
+}
+impl ClashProxies {
+    /// Proxy groups in the order of the config, `GLOBAL` comes last
+    ///
+    /// mihomo keeps that order in the members of `GLOBAL`
+    pub fn groups(&self) -> Vec<&ClashProxy> {
+        let mut groups: Vec<&ClashProxy> = self
+            .proxies
+            .get(GLOBAL)
+            .map(|g| {
+                g.all
+                    .iter()
+                    .filter_map(|name| self.proxies.get(name))
+                    .filter(|p| p.is_group())
+                    .collect()
+            })
+            .unwrap_or_default();
+        // groups not listed in `GLOBAL`, e.g. hidden ones
+        let mut rest: Vec<&ClashProxy> = self
+            .proxies
+            .values()
+            .filter(|p| p.is_group() && p.name != GLOBAL)
+            .filter(|p| !groups.iter().any(|g| g.name == p.name))
+            .collect();
+        rest.sort_by(|a, b| a.name.cmp(&b.name));
+        groups.extend(rest);
+        if let Some(g) = self.proxies.get(GLOBAL) {
+            groups.push(g);
+        }
+        groups
+    }
+    pub fn get(&self, name: &str) -> Option<&ClashProxy> {
+        self.proxies.get(name)
+    }
+}
+
+/// A node or a proxy group
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ClashProxy {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: String,
+    /// Selected member, groups only
+    pub now: Option<String>,
+    /// Members, groups only
+    pub all: Vec<String>,
+    pub history: Vec<DelayHistory>,
+    pub alive: bool,
+    pub udp: bool,
+}
+impl ClashProxy {
+    pub fn is_group(&self) -> bool {
+        matches!(
+            self.proxy_type.as_str(),
+            "Selector" | "URLTest" | "Fallback" | "LoadBalance" | "Relay"
+        )
+    }
+    /// Whether `PUT /proxies/{name}` can pick a member, a `Relay` chains all of them
+    pub fn is_selectable(&self) -> bool {
+        matches!(self.proxy_type.as_str(), "Selector" | "URLTest" | "Fallback")
+    }
+    /// Result of the latest test recorded by the core, `0` means failed
+    pub fn last_delay(&self) -> Option<u64> {
+        self.history.last().map(|h| h.delay)
+    }
+}
+
+/// Response of `GET /proxies/{name}/delay`
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ProxyDelay {
+    pub delay: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct DelayHistory {
+    pub time: String,
+    pub delay: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClashProxies;
+    const PROXIES: &str = r#"{"proxies":{
+        "DIRECT":{"name":"DIRECT","type":"Direct","history":[],"udp":true},
+        "node-a":{"name":"node-a","type":"Shadowsocks","history":[{"time":"2024-05-01T10:00:00Z","delay":120}],"alive":true,"udp":true},
+        "Auto":{"name":"Auto","type":"URLTest","now":"node-a","all":["node-a"],"history":[]},
+        "Entry":{"name":"Entry","type":"Selector","now":"Auto","all":["Auto","node-a","DIRECT"],"history":[]},
+        "GLOBAL":{"name":"GLOBAL","type":"Selector","now":"DIRECT","all":["DIRECT","node-a","Entry","Auto"],"history":[]}
+    }}"#;
+
+    #[test]
+    fn groups_order() {
+        let proxies: ClashProxies = serde_json::from_str(PROXIES).unwrap();
+        let names: Vec<&str> = proxies.groups().iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Entry", "Auto", "GLOBAL"]);
+        let entry = proxies.get("Entry").unwrap();
+        assert_eq!(entry.now.as_deref(), Some("Auto"));
+        assert!(!proxies.get("node-a").unwrap().is_group());
+        assert!(entry.is_selectable());
+        let relay: super::ClashProxy =
+            serde_json::from_str(r#"{"name":"Chain","type":"Relay","all":["Auto","node-a"]}"#).unwrap();
+        assert!(relay.is_group() && !relay.is_selectable());
+    }
+}
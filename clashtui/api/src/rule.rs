@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Response of `GET /rules`, in the order the core matches them
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ClashRules {
+    pub rules: Vec<ClashRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ClashRule {
+    #[serde(rename = "type")]
+    pub rule_type: String,
+    pub payload: String,
+    pub proxy: String,
+    /// `-1` unless it's a rule-set
+    pub size: i64,
+}
+impl ClashRule {
+    /// Number of entries of a rule-set
+    pub fn size(&self) -> Option<u64> {
+        u64::try_from(self.size).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClashRules;
+    #[test]
+    fn parse_rules() {
+        let rules: ClashRules = serde_json::from_str(
+            r#"{"rules":[{"type":"RuleSet","payload":"cn","proxy":"DIRECT","size":1024},{"type":"Match","payload":"","proxy":"Entry","size":-1}]}"#,
+        )
+        .unwrap();
+        assert_eq!(rules.rules[0].size(), Some(1024));
+        assert_eq!(rules.rules[1].size(), None);
+        assert_eq!(rules.rules[1].proxy, "Entry");
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::LogLevel;
+
+/// One message of `GET /traffic`, bytes per second
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+pub struct Traffic {
+    pub up: u64,
+    pub down: u64,
+}
+
+/// One message of `GET /memory`, bytes
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(default)]
+pub struct Memory {
+    pub inuse: u64,
+    pub oslimit: u64,
+}
+
+/// One message of `GET /logs`
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ClashLog {
+    #[serde(rename = "type")]
+    pub level: LogLevel,
+    pub payload: String,
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+/// The `subscription-userinfo` header of a subscription
+///
+/// e.g. `upload=455727941; download=6174315083; total=1073741824000; expire=1671815872`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionInfo {
+    pub upload: u64,
+    pub download: u64,
+    /// 0 if unlimited
+    pub total: u64,
+    /// Unix time, `None` if it never expires
+    pub expire: Option<i64>,
+}
+
+impl SubscriptionInfo {
+    /// Unknown keys are skipped, `None` if nothing is known
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut info = Self::default();
+        let mut found = false;
+        for (key, value) in header
+            .split(';')
+            .filter_map(|kv| kv.split_once('='))
+            .map(|(k, v)| (k.trim(), v.trim()))
+        {
+            // some servers send floats
+            let Some(value) = value.parse::<f64>().ok().filter(|v| *v >= 0.0) else {
+                continue;
+            };
+            match key {
+                "upload" => info.upload = value as u64,
+                "download" => info.download = value as u64,
+                "total" => info.total = value as u64,
+                "expire" if value > 0.0 => info.expire = Some(value as i64),
+                "expire" => (),
+                _ => continue,
+            }
+            found = true;
+        }
+        found.then_some(info)
+    }
+
+    pub fn used(&self) -> u64 {
+        self.upload.saturating_add(self.download)
+    }
+    /// Fraction of the quota used, `None` if unlimited
+    pub fn usage(&self) -> Option<f64> {
+        (self.total > 0).then(|| self.used() as f64 / self.total as f64)
+    }
+    /// Whole days until expiry, negative once expired
+    pub fn days_left(&self, now: i64) -> Option<i64> {
+        self.expire.map(|e| (e - now).div_euclid(24 * 60 * 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriptionInfo;
+    #[test]
+    fn parse_test() {
+        let info = SubscriptionInfo::parse(
+            "upload=455727941; download=6174315083; total=1073741824000; expire=1671815872",
+        )
+        .unwrap();
+        assert_eq!(info.used(), 455727941 + 6174315083);
+        assert_eq!(info.expire, Some(1671815872));
+        assert_eq!(info.days_left(1671815872 - 3 * 86400 - 1), Some(3));
+        assert_eq!(info.days_left(1671815872 + 1), Some(-1));
+
+        let info = SubscriptionInfo::parse("upload=0;download=1.5e9;total=0;expire=").unwrap();
+        assert_eq!(info.download, 1_500_000_000);
+        assert!(info.usage().is_none() && info.expire.is_none());
+        assert!(SubscriptionInfo::parse("").is_none());
+        assert!(SubscriptionInfo::parse("foo=1").is_none());
+    }
+}
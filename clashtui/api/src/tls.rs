@@ -0,0 +1,184 @@
+//! HTTPS to `external-controller-tls` with a CA bundle or a pinned certificate
+//!
+//! minreq only trusts the public roots, while a controller usually has
+//! a self-signed certificate.
+use std::io::Error;
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, ClientConnection, RootCertStore, ServerName, StreamOwned};
+
+use crate::http1::{self, Response};
+use crate::{ApiError, Result};
+
+/// How to check the certificate of the controller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsTrust {
+    /// The public roots, as any https site
+    System,
+    /// A PEM file of CA certificates
+    CaBundle(PathBuf),
+    /// SHA-256 of the controller certificate in hex, colons are allowed
+    Fingerprint(String),
+}
+
+#[derive(Clone)]
+pub(crate) struct TlsConnector {
+    host: String,
+    port: u16,
+    server_name: ServerName,
+    config: Arc<ClientConfig>,
+}
+
+impl TlsConnector {
+    pub(crate) fn new(host: &str, port: u16, trust: &TlsTrust) -> Result<Self> {
+        let server_name = ServerName::try_from(host.trim_matches(['[', ']']))
+            .map_err(|_| ApiError::InvalidInput("Invalid controller host"))?;
+        let builder = ClientConfig::builder().with_safe_defaults();
+        let config = match trust {
+            TlsTrust::System => {
+                let mut roots = RootCertStore::empty();
+                roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                    rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                        ta.subject,
+                        ta.spki,
+                        ta.name_constraints,
+                    )
+                }));
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            TlsTrust::CaBundle(path) => {
+                let pem = std::fs::read_to_string(path)?;
+                let mut roots = RootCertStore::empty();
+                for der in pem_blocks(&pem, "CERTIFICATE") {
+                    roots
+                        .add(&Certificate(der))
+                        .map_err(|_| ApiError::InvalidInput("Invalid CA certificate"))?;
+                }
+                if roots.is_empty() {
+                    return Err(ApiError::InvalidInput("No certificate in the CA bundle"));
+                }
+                builder.with_root_certificates(roots).with_no_client_auth()
+            }
+            TlsTrust::Fingerprint(hex) => {
+                let pinned = parse_fingerprint(hex)
+                    .ok_or(ApiError::InvalidInput("Invalid certificate fingerprint"))?;
+                let mut config = builder
+                    .with_root_certificates(RootCertStore::empty())
+                    .with_no_client_auth();
+                config
+                    .dangerous()
+                    .set_certificate_verifier(Arc::new(PinnedCert(pinned)));
+                config
+            }
+        };
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            server_name,
+            config: Arc::new(config),
+        })
+    }
+
+    /// `timeout` applies to every read and write, `None` waits forever
+    pub(crate) fn send(
+        &self,
+        method: &str,
+        sub_url: &str,
+        secret: &str,
+        body: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> std::io::Result<Response> {
+        let sock = TcpStream::connect((self.host.trim_matches(['[', ']']), self.port))?;
+        sock.set_read_timeout(timeout)?;
+        sock.set_write_timeout(timeout)?;
+        let conn = ClientConnection::new(self.config.clone(), self.server_name.clone())
+            .map_err(Error::other)?;
+        http1::exchange(
+            StreamOwned::new(conn, sock),
+            &format!("{}:{}", self.host, self.port),
+            method,
+            sub_url,
+            secret,
+            body,
+        )
+    }
+}
+
+/// Accept only the certificate with the given SHA-256, whoever signed it
+struct PinnedCert([u8; 32]);
+impl ServerCertVerifier for PinnedCert {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &end_entity.0);
+        if digest.as_ref() == self.0 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "Certificate fingerprint mismatch".to_string(),
+            ))
+        }
+    }
+}
+
+/// Like `AD:CA:C6:...` or `adcac6...`
+fn parse_fingerprint(hex: &str) -> Option<[u8; 32]> {
+    let hex: Vec<u8> = hex.bytes().filter(|b| !matches!(b, b':' | b' ')).collect();
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (i, pair) in hex.chunks(2).enumerate() {
+        let s = std::str::from_utf8(pair).ok()?;
+        out[i] = u8::from_str_radix(s, 16).ok()?;
+    }
+    Some(out)
+}
+
+/// DER contents of the `-----BEGIN {label}-----` blocks, broken ones are skipped
+pub(crate) fn pem_blocks(pem: &str, label: &str) -> Vec<Vec<u8>> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let mut blocks = vec![];
+    let mut body: Option<String> = None;
+    for line in pem.lines().map(str::trim) {
+        if line == begin {
+            body = Some(String::new());
+        } else if line == end {
+            if let Some(der) = body.take().and_then(|b| crate::base64_decode(&b)) {
+                blocks.push(der);
+            }
+        } else if let Some(b) = body.as_mut() {
+            b.push_str(line);
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn pem_test() {
+        let pem = "-----BEGIN CERTIFICATE-----\naGVs\nbG8=\n-----END CERTIFICATE-----\n";
+        assert_eq!(pem_blocks(pem, "CERTIFICATE"), vec![b"hello".to_vec()]);
+        assert!(pem_blocks(pem, "PRIVATE KEY").is_empty());
+
+        let fp = crate::mock::MOCK_CERT_SHA256;
+        assert_eq!(
+            parse_fingerprint(fp),
+            parse_fingerprint(&fp.replace(':', "").to_lowercase())
+        );
+        assert!(parse_fingerprint("AD:CA").is_none());
+    }
+}
@@ -0,0 +1,22 @@
+//! HTTP over a Unix domain socket, for `external-controller-unix`
+use std::io::Result;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::http1::{self, Response};
+
+/// `timeout` applies to every read and write, `None` waits forever
+pub(crate) fn send(
+    socket: &Path,
+    method: &str,
+    sub_url: &str,
+    secret: &str,
+    body: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<Response> {
+    let stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    http1::exchange(stream, "localhost", method, sub_url, secret, body)
+}
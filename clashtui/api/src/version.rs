@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Response of `/version`
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct ClashVersion {
+    /// Always true for mihomo
+    pub meta: bool,
+    pub version: String,
+}
+impl std::fmt::Display for ClashVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClashVersion;
+    #[test]
+    fn parse_version() {
+        let v: ClashVersion =
+            serde_json::from_str(r#"{"meta":true,"version":"v1.18.5"}"#).unwrap();
+        assert!(v.meta);
+        assert_eq!(v.to_string(), "v1.18.5");
+    }
+}
@@ -29,8 +29,7 @@ fn main() {
     println!("cargo:rerun-if-changed=../.git/refs/heads/dev");
     println!("cargo:rerun-if-changed=build.rs",);
 
-    if let Ok(_) = env::var("CLASHTUI_VERSION") {
-    } else {
+    if env::var("CLASHTUI_VERSION").is_err() {
         println!(
             "cargo:rustc-env=CLASHTUI_VERSION={}",
             get_version()
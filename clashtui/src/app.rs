@@ -1,19 +1,23 @@
 use core::cell::{OnceCell, RefCell};
-use std::{path::PathBuf, rc::Rc};
+use std::{path::PathBuf, rc::Rc, sync::mpsc::{Receiver, TryRecvError}};
 use std::io::{Write, BufRead, Read};
 
 use ui::event;
 
 use crate::{msgpopup_methods, utils};
 use crate::tui::{
-    tabs::{ClashSrvCtlTab, ProfileTab, TabEvent, Tabs},
+    tabs::{
+        ClashSrvCtlTab, ConnectionTab, CoreLogTab, ProfileTab, ProviderTab, ProxyTab, RuleTab,
+        TabEvent, Tabs,
+    },
     tools,
     utils::{HelpPopUp, InfoPopUp, Keys},
     widgets::MsgPopup,
     EventState, StatusBar, TabBar, Theme, Visibility,
 };
 use crate::utils::{
-    CfgError, ClashTuiUtil, Flag, Flags, SharedClashTuiState, SharedClashTuiUtil, State,
+    CfgError, ClashTuiUtil, Flag, Flags, LatestRelease, SharedClashTuiState, SharedClashTuiUtil,
+    State,
 };
 
 /// Mihomo (Clash.Meta) TUI Client
@@ -36,6 +40,7 @@ pub struct App {
     help_popup: OnceCell<Box<HelpPopUp>>,
     info_popup: InfoPopUp,
     msgpopup: MsgPopup,
+    release_rx: Option<Receiver<Result<LatestRelease, String>>>,
 
     pub clashtui_util: SharedClashTuiUtil,
     statusbar: StatusBar,
@@ -63,9 +68,14 @@ impl App {
                 clashtui_util.clone(),
                 clashtui_state.clone(),
             )),
+            Tabs::Proxy(ProxyTab::new(clashtui_util.clone())),
+            Tabs::Connection(ConnectionTab::new(clashtui_util.clone())),
+            Tabs::Rule(RuleTab::new(clashtui_util.clone())),
+            Tabs::Provider(ProviderTab::new(clashtui_util.clone())),
+            Tabs::CoreLog(CoreLogTab::new(clashtui_util.clone())),
         ]; // Init the tabs
         let tabbar = TabBar::new(tabs.iter().map(|v| v.to_string()).collect());
-        let statusbar = StatusBar::new(Rc::clone(&clashtui_state));
+        let statusbar = StatusBar::new(Rc::clone(&clashtui_state), clashtui_util.monitor_core());
         let info_popup = InfoPopUp::with_items(&clashtui_util.clash_version());
 
         let mut app = Self {
@@ -74,13 +84,19 @@ impl App {
             help_popup: Default::default(),
             info_popup,
             msgpopup: Default::default(),
+            release_rx: None,
             statusbar,
             clashtui_util,
             tabs,
         };
 
         app.do_some_job_after_initapp_before_setupui();
-        
+        // Not in cron mode, which exits above before the result is saved
+        app.release_rx = app.clashtui_util.check_release();
+        if app.release_rx.is_none() {
+            app.info_popup.set_release(&app.clashtui_util.latest_release());
+        }
+
         (Some(app), err_track)
     }
 
@@ -98,6 +114,11 @@ impl App {
         let mut iter = self.tabs.iter_mut().map(|v| match v {
             Tabs::Profile(tab) => tab.popup_event(ev),
             Tabs::ClashSrvCtl(tab) => tab.popup_event(ev),
+            Tabs::Proxy(tab) => tab.popup_event(ev),
+            Tabs::Connection(tab) => tab.popup_event(ev),
+            Tabs::Rule(tab) => tab.popup_event(ev),
+            Tabs::Provider(tab) => tab.popup_event(ev),
+            Tabs::CoreLog(tab) => tab.popup_event(ev),
         });
         while event_state.is_notconsumed() {
             match iter.next() {
@@ -173,10 +194,15 @@ impl App {
                 event_state = self
                     .tabbar
                     .event(ev)
-                    .map_err(|()| std::io::Error::new(std::io::ErrorKind::Other, "Undefined"))?;
+                    .map_err(|()| std::io::Error::other("Undefined"))?;
                 let mut iter = self.tabs.iter_mut().map(|v| match v {
                     Tabs::Profile(tab) => tab.event(ev),
-                    Tabs::ClashSrvCtl(tab) => Ok(tab.event(ev)?),
+                    Tabs::ClashSrvCtl(tab) => tab.event(ev),
+                    Tabs::Proxy(tab) => tab.event(ev),
+                    Tabs::Connection(tab) => tab.event(ev),
+                    Tabs::Rule(tab) => tab.event(ev),
+                    Tabs::Provider(tab) => tab.event(ev),
+                    Tabs::CoreLog(tab) => tab.event(ev),
                 });
                 while event_state.is_notconsumed() {
                     match iter.next() {
@@ -189,10 +215,41 @@ impl App {
 
         Ok(event_state)
     }
+    /// Tell about a new release of clashtui once it's fetched
+    fn recv_release(&mut self) {
+        let Some(rx) = self.release_rx.as_ref() else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok(release)) => {
+                self.info_popup.set_release(&release);
+                if utils::is_newer(&release.tag_name, crate::VERSION) == Some(true) {
+                    self.popup_txt_msg(format!(
+                        "ClashTui {} is available, see `I` for the changelog",
+                        release.tag_name
+                    ));
+                }
+                self.clashtui_util.release_checked(release);
+            }
+            // keep the cache, the check is retried next time
+            Ok(Err(_)) | Err(TryRecvError::Disconnected) => {
+                self.info_popup.set_release(&self.clashtui_util.latest_release());
+            }
+            Err(TryRecvError::Empty) => return,
+        }
+        self.release_rx = None;
+    }
     pub fn late_event(&mut self) {
+        self.recv_release();
+        self.statusbar.recv_stats();
         self.tabs.iter_mut().for_each(|v| match v {
             Tabs::Profile(tab) => tab.late_event(),
             Tabs::ClashSrvCtl(tab) => tab.late_event(),
+            Tabs::Proxy(tab) => tab.late_event(),
+            Tabs::Connection(tab) => tab.late_event(),
+            Tabs::Rule(tab) => tab.late_event(),
+            Tabs::Provider(tab) => tab.late_event(),
+            Tabs::CoreLog(tab) => tab.late_event(),
         })
     }
 
@@ -216,6 +273,11 @@ impl App {
         self.tabs.iter_mut().for_each(|v| match v {
             Tabs::Profile(tab) => tab.draw(f, tab_chunk),
             Tabs::ClashSrvCtl(tab) => tab.draw(f, tab_chunk),
+            Tabs::Proxy(tab) => tab.draw(f, tab_chunk),
+            Tabs::Connection(tab) => tab.draw(f, tab_chunk),
+            Tabs::Rule(tab) => tab.draw(f, tab_chunk),
+            Tabs::Provider(tab) => tab.draw(f, tab_chunk),
+            Tabs::CoreLog(tab) => tab.draw(f, tab_chunk),
         });
 
         self.statusbar.draw(f, chunks[2]);
@@ -239,6 +301,11 @@ impl App {
             .for_each(|(b, v)| match v {
                 Tabs::Profile(tab) => tab.set_visible(b),
                 Tabs::ClashSrvCtl(tab) => tab.set_visible(b),
+                Tabs::Proxy(tab) => tab.set_visible(b),
+                Tabs::Connection(tab) => tab.set_visible(b),
+                Tabs::Rule(tab) => tab.set_visible(b),
+                Tabs::Provider(tab) => tab.set_visible(b),
+                Tabs::CoreLog(tab) => tab.set_visible(b),
             });
     }
 
@@ -296,7 +363,12 @@ impl App {
             println!("## {}", datetime.format("%Y-%m-%d %H:%M:%S"));
             if let Ok(profile_names) = self.clashtui_util.get_profile_names() {
                 let mut ok_profiles = Vec::new();
+                let now = chrono::Utc::now().timestamp();
                 for p_name in profile_names {
+                    if !self.clashtui_util.is_profile_due(&p_name, now) {
+                        println!("Skip Profile `{p_name}`: not due");
+                        continue;
+                    }
                     println!("Update Profile `{p_name}`:");
                     match self.clashtui_util.update_profile(&p_name, false) {
                         Ok(r) => {
@@ -337,6 +409,7 @@ impl App {
         }
 
         if is_cli_mode {
+            self.clashtui_util.save_to_data_file();
             std::process::exit(0);
         }
     }
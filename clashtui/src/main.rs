@@ -43,7 +43,7 @@ fn main() {
     std::process::exit(0);
 }
 pub fn run(flags: &mut Flags<Flag>, tick_rate: u64, config_dir: &std::path::PathBuf, warning_list_msg: &mut Vec<String>) -> std::io::Result<()> {
-    let (app, err_track) = App::new(&flags, config_dir);
+    let (app, err_track) = App::new(flags, config_dir);
     log::debug!("Current flags: {:?}", flags);
 
     if let Some(mut app) = app {
@@ -1,18 +1,59 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::Receiver;
+
 use ratatui::{prelude as Ra, widgets as Raw};
 
 use super::Theme;
-use crate::utils::SharedClashTuiState;
+use crate::utils::{str_bytes, CoreStat, SharedClashTuiState};
+
+// One value per second
+const HISTORY_LEN: usize = 120;
+const LABEL_WIDTH: u16 = 12;
 
 pub struct StatusBar {
     is_visible: bool,
     clashtui_state: SharedClashTuiState,
+
+    stat_rx: Receiver<CoreStat>,
+    up: VecDeque<u64>,
+    down: VecDeque<u64>,
+    memory: VecDeque<u64>,
 }
 
 impl StatusBar {
-    pub fn new(clashtui_state: SharedClashTuiState) -> Self {
+    pub fn new(clashtui_state: SharedClashTuiState, stat_rx: Receiver<CoreStat>) -> Self {
         Self {
             is_visible: true,
             clashtui_state,
+
+            stat_rx,
+            up: VecDeque::with_capacity(HISTORY_LEN),
+            down: VecDeque::with_capacity(HISTORY_LEN),
+            memory: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    /// Collect the stats of the core without blocking
+    pub fn recv_stats(&mut self) {
+        fn push(history: &mut VecDeque<u64>, v: u64) {
+            if history.len() == HISTORY_LEN {
+                history.pop_front();
+            }
+            history.push_back(v);
+        }
+        while let Ok(stat) = self.stat_rx.try_recv() {
+            match stat {
+                CoreStat::Traffic(t) => {
+                    push(&mut self.up, t.up);
+                    push(&mut self.down, t.down);
+                }
+                CoreStat::Memory(m) => push(&mut self.memory, m.inuse),
+                CoreStat::Disconnected => {
+                    self.up.clear();
+                    self.down.clear();
+                    self.memory.clear();
+                }
+            }
         }
     }
 
@@ -22,6 +63,15 @@ impl StatusBar {
         }
 
         f.render_widget(Raw::Clear, area);
+        let block = Raw::Block::new().borders(Raw::Borders::ALL);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let chunks = Ra::Layout::default()
+            .direction(Ra::Direction::Horizontal)
+            .constraints([Ra::Constraint::Percentage(50), Ra::Constraint::Percentage(50)])
+            .split(inner);
+
         let state = self.clashtui_state.borrow();
         let status_str = state.render();
         let paragraph = Raw::Paragraph::new(Ra::Span::styled(
@@ -30,7 +80,53 @@ impl StatusBar {
         ))
         //.alignment(ratatui::prelude::Alignment::Right)
         .wrap(Raw::Wrap { trim: true });
-        let block = Raw::Block::new().borders(Raw::Borders::ALL);
-        f.render_widget(paragraph.block(block), area);
+        f.render_widget(paragraph, chunks[0]);
+
+        let theme = Theme::get();
+        let graphs = Ra::Layout::default()
+            .direction(Ra::Direction::Horizontal)
+            .constraints([Ra::Constraint::Ratio(1, 3); 3])
+            .split(chunks[1]);
+        for (history, area, prefix, suffix, fg) in [
+            (&self.up, graphs[0], "↑", "/s", theme.statusbar_traffic_up_fg),
+            (&self.down, graphs[1], "↓", "/s", theme.statusbar_traffic_down_fg),
+            (&self.memory, graphs[2], "Mem ", "", theme.statusbar_memory_fg),
+        ] {
+            draw_sparkline(f, area, history, format!("{prefix}{}{suffix}", latest(history)), fg);
+        }
     }
 }
+
+fn latest(history: &VecDeque<u64>) -> String {
+    history.back().map_or("-".to_string(), |v| str_bytes(*v))
+}
+
+fn draw_sparkline(
+    f: &mut Ra::Frame,
+    area: Ra::Rect,
+    history: &VecDeque<u64>,
+    label: String,
+    fg: Ra::Color,
+) {
+    let chunks = Ra::Layout::default()
+        .direction(Ra::Direction::Horizontal)
+        .constraints([Ra::Constraint::Length(LABEL_WIDTH), Ra::Constraint::Min(0)])
+        .split(area);
+    f.render_widget(
+        Raw::Paragraph::new(Ra::Span::styled(label, Ra::Style::default().fg(fg))),
+        chunks[0],
+    );
+    // Sparkline draws from the oldest value, only keep the ones fit in
+    let width = chunks[1].width as usize;
+    let data: Vec<u64> = history
+        .iter()
+        .skip(history.len().saturating_sub(width))
+        .copied()
+        .collect();
+    f.render_widget(
+        Raw::Sparkline::default()
+            .data(&data)
+            .style(Ra::Style::default().fg(fg)),
+        chunks[1],
+    );
+}
@@ -25,12 +25,48 @@ v: Preview
 
 ## ClashSrvCtl
 Enter: Action
+SwitchTun: TUN needs cap_net_admin, see SetPermission
+InstallCore: Download mihomo, the previous one is kept for RollbackCore
+
+## RuntimeConfig (in ClashSrvCtl)
+Enter: Toggle / Switch / Edit, applied to the core at once
+w: Save the changes into basic_clash_config.yaml
+
+## Proxy
+Enter: Open group / Select node
+Esc: Back to groups
+r: Refresh
+c: Test latency of the group / node
+o: Sort nodes by latency
+
+## Connection
+/: Filter by host, process, rule or chain
+x: Close the connection
+z: Close all connections
+
+## Rule
+/: Search by type, payload or proxy
+r: Refresh
+
+## Provider
+Enter: Show details
+u: Update
+a: Update all HTTP providers
+c: Health check
+b: Health check all proxy providers
+r: Refresh
+
+## Log
+/: Search
+f: Switch level (debug/info/warning/error)
+Space: Pause / Resume
+w: Save to file
 
 ## Global
 q: Quit
 R: Restart clash core
-L: Show recent log
-I: Show informations
+L: Show recent log of clashtui
+I: Show informations, with the changelog of a new release
 H: Locate app home path
 G: Locate clash config dir
 1,2,...,9 OR Tab: Switch tabs
@@ -44,3 +80,9 @@ external-controller: 127.0.0.1:9090"#;
 pub(super) const PROFILE: &str = "Profile";
 pub(super) const TEMPALTE: &str = "Template";
 pub(super) const CLASHSRVCTL: &str = "ClashSrvCtl";
+pub(super) const PROXY: &str = "Proxy";
+pub(super) const PROXY_MEMBER: &str = "Node";
+pub(super) const CONNECTION: &str = "Connection";
+pub(super) const RULE: &str = "Rule";
+pub(super) const PROVIDER: &str = "Provider";
+pub(super) const CORE_LOG: &str = "Log";
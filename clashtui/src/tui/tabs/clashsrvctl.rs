@@ -1,16 +1,23 @@
-use super::ClashSrvOp;
+use super::{
+    dns_input::DnsQueryPopup,
+    runtime_config::{ConfigAction, RuntimeConfigPopup},
+    ClashSrvOp,
+};
 use crate::msgpopup_methods;
 use crate::{
     tui::{
         symbols::CLASHSRVCTL,
         tools,
         utils::Keys,
-        widgets::{List, MsgPopup},
+        widgets::{ConfirmPopup, List, MsgPopup},
         EventState, Visibility,
     },
-    utils::{SharedClashTuiState, SharedClashTuiUtil},
+    utils::{GeoUpdate, SharedClashTuiState, SharedClashTuiUtil},
 };
-use api::Mode;
+use api::{Mode, TunStack};
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+const TUN_STACKS: [TunStack; 3] = [TunStack::Mixed, TunStack::Gvisor, TunStack::System];
 
 #[derive(Visibility)]
 pub struct ClashSrvCtlTab {
@@ -18,13 +25,22 @@ pub struct ClashSrvCtlTab {
 
     main_list: List,
     msgpopup: MsgPopup,
+    confirm_popup: ConfirmPopup,
 
     mode_selector: List,
+    stack_selector: List,
+    dns_popup: Box<DnsQueryPopup>,
+    config_popup: Box<RuntimeConfigPopup>,
 
     clashtui_util: SharedClashTuiUtil,
     clashtui_state: SharedClashTuiState,
 
     op: Option<ClashSrvOp>,
+    confirm_op: Option<ClashSrvOp>,
+
+    upgrade_rx: Option<Receiver<String>>,
+    upgrade_log: Vec<String>,
+    geo_rx: Option<Receiver<GeoUpdate>>,
 }
 
 impl ClashSrvCtlTab {
@@ -35,6 +51,19 @@ impl ClashSrvCtlTab {
             ClashSrvOp::StartClashService.into(),
             ClashSrvOp::StopClashService.into(),
             ClashSrvOp::SwitchMode.into(),
+            ClashSrvOp::SwitchTun.into(),
+            ClashSrvOp::SwitchTunStack.into(),
+            ClashSrvOp::RuntimeConfig.into(),
+            ClashSrvOp::DnsQuery.into(),
+            ClashSrvOp::FlushFakeIp.into(),
+            ClashSrvOp::FlushDnsCache.into(),
+            ClashSrvOp::GeoDataStatus.into(),
+            ClashSrvOp::UpdateGeoDataByCore.into(),
+            ClashSrvOp::UpdateGeoDataFromGithub.into(),
+            ClashSrvOp::UpgradeCore.into(),
+            ClashSrvOp::UpgradeDashboard.into(),
+            ClashSrvOp::InstallCore.into(),
+            ClashSrvOp::RollbackCore.into(),
         ]);
         let mut modes = List::new("Mode".to_string());
         modes.set_items(vec![
@@ -43,15 +72,166 @@ impl ClashSrvCtlTab {
             Mode::Global.into(),
         ]);
         modes.hide();
+        let mut stacks = List::new("Tun Stack".to_string());
+        stacks.set_items(TUN_STACKS.into_iter().map(String::from).collect());
+        stacks.hide();
 
         Self {
             is_visible: false,
             main_list: operations,
             mode_selector: modes,
+            stack_selector: stacks,
+            dns_popup: Box::new(DnsQueryPopup::new()),
+            config_popup: Box::new(RuntimeConfigPopup::new()),
             clashtui_util,
             clashtui_state,
             msgpopup: Default::default(),
+            confirm_popup: ConfirmPopup::new(),
             op: None,
+            confirm_op: None,
+
+            upgrade_rx: None,
+            upgrade_log: vec![],
+            geo_rx: None,
+        }
+    }
+
+    fn handle_dns_query_ev(&mut self) {
+        let (name, record_type) = self.dns_popup.get_query();
+        match self.clashtui_util.dns_query(&name, &record_type) {
+            Ok(query) => {
+                let mut lines = vec![format!("{} {}: {}", name, record_type, query.status_str())];
+                lines.extend(query.answer.iter().map(|a| {
+                    format!("{} {} {} {}", a.name, a.ttl, a.type_str(), a.data)
+                }));
+                if query.answer.is_empty() {
+                    lines.push("No answer".to_string());
+                }
+                self.popup_list_msg(lines);
+            }
+            Err(err) => {
+                log::error!("DnsQuery `{name}` => {err}");
+                self.popup_txt_msg(format!("Failed to Query: {err}"));
+            }
+        }
+    }
+
+    fn refresh_runtime_config(&mut self) -> bool {
+        match self.clashtui_util.runtime_config() {
+            Ok(cfg) => {
+                self.config_popup.set_config(cfg);
+                true
+            }
+            Err(err) => {
+                self.popup_txt_msg(format!("Failed to fetch the config: {err}"));
+                false
+            }
+        }
+    }
+    fn handle_config_action(&mut self) {
+        match self.config_popup.take_action() {
+            None => (),
+            Some(ConfigAction::Invalid(msg)) => self.popup_txt_msg(msg),
+            Some(ConfigAction::Patch(patch)) => {
+                match self.clashtui_util.patch_runtime_config(&patch) {
+                    Ok(()) => self.config_popup.applied(patch),
+                    Err(err) => self.popup_txt_msg(format!("Failed to Patch: {err}")),
+                }
+                self.refresh_runtime_config();
+            }
+            Some(ConfigAction::Persist) => {
+                let count = self.config_popup.changed().as_object().map_or(0, |m| m.len());
+                if count == 0 {
+                    self.popup_txt_msg("Nothing to save".to_string());
+                    return;
+                }
+                // serde_yaml doesn't keep comments
+                self.confirm_popup.popup_msg(format!(
+                    "`y` to Save {count} change(s) into basic_clash_config.yaml (comments are dropped), `Esc` to cancel"
+                ));
+                self.confirm_op.replace(ClashSrvOp::SaveRuntimeConfig);
+            }
+        }
+    }
+
+    fn handle_tun_stack_ev(&mut self, stack: TunStack) {
+        if let Err(err) = self.clashtui_util.set_tun_stack(stack) {
+            log::error!("SwitchTunStack `{stack}` => {err}");
+            self.popup_txt_msg(format!("Failed to Switch: {err}"));
+        }
+        self.clashtui_state.borrow_mut().refresh();
+    }
+
+    /// Upgrade or install in background, see [recv_upgrade][Self::recv_upgrade]
+    fn handle_upgrade_ev(&mut self, op: ClashSrvOp) {
+        if self.upgrade_rx.is_some() {
+            self.popup_txt_msg("An upgrade is running".to_string());
+            return;
+        }
+        self.upgrade_log.clear();
+        self.upgrade_rx = Some(match op {
+            ClashSrvOp::UpgradeDashboard => self.clashtui_util.upgrade_core(true),
+            ClashSrvOp::InstallCore => self.clashtui_util.install_core(),
+            _ => self.clashtui_util.upgrade_core(false),
+        });
+    }
+    /// Show the progress of upgrading without blocking
+    fn recv_upgrade(&mut self) {
+        let Some(rx) = self.upgrade_rx.as_ref() else {
+            return;
+        };
+        let mut updated = false;
+        let done = loop {
+            match rx.try_recv() {
+                Ok(line) => {
+                    self.upgrade_log.push(line);
+                    updated = true;
+                }
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        if updated {
+            self.popup_list_msg(self.upgrade_log.clone());
+        }
+        if done {
+            self.upgrade_rx = None;
+            self.clashtui_state.borrow_mut().refresh();
+        }
+    }
+
+    /// Update geo data in background, see [recv_geo_update][Self::recv_geo_update]
+    fn handle_geo_update_ev(&mut self, op: ClashSrvOp) {
+        if self.geo_rx.is_some() {
+            self.popup_txt_msg("A geo data update is running".to_string());
+            return;
+        }
+        self.geo_rx = Some(match op {
+            ClashSrvOp::UpdateGeoDataByCore => self.clashtui_util.update_geo_data_by_core(),
+            _ => self.clashtui_util.update_geo_data_from_github(),
+        });
+        self.popup_txt_msg("Updating geo data...".to_string());
+    }
+    /// Show the result once the geo data update is done
+    fn recv_geo_update(&mut self) {
+        let Some(rx) = self.geo_rx.as_ref() else {
+            return;
+        };
+        let update = match rx.try_recv() {
+            Ok(update) => update,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.geo_rx = None;
+                return;
+            }
+        };
+        self.geo_rx = None;
+        self.hide_msgpopup();
+        match self.clashtui_util.finish_geo_update(update) {
+            Ok(output) => {
+                self.popup_list_msg(output.lines().map(|line| line.trim().to_string()));
+            }
+            Err(err) => self.popup_txt_msg(err.to_string()),
         }
     }
 }
@@ -60,9 +240,8 @@ impl super::TabEvent for ClashSrvCtlTab {
         if !self.is_visible {
             return Ok(EventState::NotConsumed);
         }
-        let event_state;
         if self.mode_selector.is_visible() {
-            event_state = self.mode_selector.event(ev)?;
+            let event_state = self.mode_selector.event(ev)?;
             if event_state == EventState::WorkDone {
                 return Ok(event_state);
             }
@@ -80,11 +259,55 @@ impl super::TabEvent for ClashSrvCtlTab {
             return Ok(EventState::WorkDone);
         }
 
-        event_state = self.msgpopup.event(ev)?;
+        if self.stack_selector.is_visible() {
+            let event_state = self.stack_selector.event(ev)?;
+            if event_state == EventState::WorkDone {
+                return Ok(event_state);
+            }
+            if let ui::event::Event::Key(key) = ev {
+                if &Keys::Select == key {
+                    if let Some(i) = self.stack_selector.selected_index() {
+                        self.handle_tun_stack_ev(TUN_STACKS[i]);
+                    }
+                    self.stack_selector.hide();
+                }
+                if &Keys::Esc == key {
+                    self.stack_selector.hide();
+                }
+            }
+            return Ok(EventState::WorkDone);
+        }
+
+        if self.dns_popup.is_visible() {
+            let event_state = self.dns_popup.event(ev)?;
+            if !self.dns_popup.is_visible() && !self.dns_popup.get_query().0.is_empty() {
+                self.op.replace(ClashSrvOp::DnsQuery);
+                self.popup_txt_msg("Working...".to_string());
+            }
+            return Ok(event_state);
+        }
+
+        let mut event_state = self.msgpopup.event(ev)?;
+        if event_state.is_notconsumed() {
+            event_state = match self.confirm_popup.event(ev)? {
+                EventState::Yes => {
+                    self.op = self.confirm_op.take();
+                    self.popup_txt_msg("Working...".to_string());
+                    EventState::WorkDone
+                }
+                EventState::Cancel | EventState::WorkDone => EventState::WorkDone,
+                _ => EventState::NotConsumed,
+            };
+        }
+        // below the messages it raises
+        if event_state.is_notconsumed() && self.config_popup.is_visible() {
+            event_state = self.config_popup.event(ev)?;
+            self.handle_config_action();
+        }
 
         Ok(event_state)
     }
-    fn event(&mut self, ev: &ui::event::Event) -> Result<EventState, ui::Infailable> {
+    fn event(&mut self, ev: &ui::event::Event) -> Result<EventState, std::io::Error> {
         if !self.is_visible {
             return Ok(EventState::NotConsumed);
         }
@@ -99,6 +322,34 @@ impl super::TabEvent for ClashSrvCtlTab {
                 let op = ClashSrvOp::from(self.main_list.selected().unwrap().as_str());
                 if let ClashSrvOp::SwitchMode = op {
                     self.mode_selector.show();
+                } else if let ClashSrvOp::SwitchTunStack = op {
+                    self.stack_selector.show();
+                } else if let ClashSrvOp::DnsQuery = op {
+                    self.dns_popup.show();
+                } else if let ClashSrvOp::FlushFakeIp | ClashSrvOp::FlushDnsCache = op {
+                    // Working connections might break
+                    self.confirm_popup
+                        .popup_msg("`y` to Flush, `Esc` to cancel".to_string());
+                    self.confirm_op.replace(op);
+                } else if let ClashSrvOp::UpdateGeoDataByCore
+                | ClashSrvOp::UpdateGeoDataFromGithub = op
+                {
+                    // Large downloads
+                    self.confirm_popup
+                        .popup_msg("`y` to Update geo data, `Esc` to cancel".to_string());
+                    self.confirm_op.replace(op);
+                } else if let ClashSrvOp::UpgradeCore | ClashSrvOp::UpgradeDashboard = op {
+                    // The core restarts after upgrading
+                    self.confirm_popup
+                        .popup_msg("`y` to Upgrade, `Esc` to cancel".to_string());
+                    self.confirm_op.replace(op);
+                } else if let ClashSrvOp::InstallCore | ClashSrvOp::RollbackCore = op {
+                    // Replaces clash_core_path
+                    self.confirm_popup.popup_msg(format!(
+                        "`y` to {} the core, `Esc` to cancel",
+                        if let ClashSrvOp::InstallCore = op { "Install" } else { "Roll back" }
+                    ));
+                    self.confirm_op.replace(op);
                 } else {
                     self.op.replace(op);
                     self.popup_txt_msg("Working...".to_string());
@@ -114,10 +365,34 @@ impl super::TabEvent for ClashSrvCtlTab {
         Ok(event_state)
     }
     fn late_event(&mut self) {
+        self.recv_upgrade();
+        self.recv_geo_update();
         if let Some(op) = self.op.take() {
             self.hide_msgpopup();
             match op {
-                ClashSrvOp::SwitchMode => unreachable!(),
+                ClashSrvOp::SwitchMode | ClashSrvOp::SwitchTunStack => unreachable!(),
+                ClashSrvOp::DnsQuery => self.handle_dns_query_ev(),
+                ClashSrvOp::RuntimeConfig => {
+                    if self.refresh_runtime_config() {
+                        self.config_popup.show();
+                    }
+                }
+                ClashSrvOp::SaveRuntimeConfig => {
+                    let changed = self.config_popup.changed();
+                    match self.clashtui_util.persist_runtime_config(&changed) {
+                        Ok(()) => {
+                            self.config_popup.saved();
+                            self.refresh_runtime_config();
+                            self.popup_txt_msg("Saved into basic_clash_config.yaml".to_string());
+                        }
+                        Err(err) => self.popup_txt_msg(format!("Failed to Save: {err}")),
+                    }
+                }
+                ClashSrvOp::UpgradeCore
+                | ClashSrvOp::UpgradeDashboard
+                | ClashSrvOp::InstallCore => self.handle_upgrade_ev(op.clone()),
+                ClashSrvOp::UpdateGeoDataByCore
+                | ClashSrvOp::UpdateGeoDataFromGithub => self.handle_geo_update_ev(op.clone()),
                 _ => match self.clashtui_util.clash_srv_ctl(op.clone()) {
                     Ok(output) => {
                         self.popup_list_msg(output.lines().map(|line| line.trim().to_string()));
@@ -129,7 +404,21 @@ impl super::TabEvent for ClashSrvCtlTab {
             }
             match op {
                 // Ops that doesn't need refresh
-                ClashSrvOp::SetPermission => {},
+                ClashSrvOp::SetPermission
+                | ClashSrvOp::DnsQuery
+                | ClashSrvOp::RuntimeConfig
+                | ClashSrvOp::SaveRuntimeConfig
+                | ClashSrvOp::FlushFakeIp
+                | ClashSrvOp::FlushDnsCache
+                | ClashSrvOp::GeoDataStatus
+                | ClashSrvOp::UpdateGeoDataByCore
+                | ClashSrvOp::UpdateGeoDataFromGithub
+                // the running core is not touched
+                | ClashSrvOp::RollbackCore
+                // refreshed once the upgrade is done
+                | ClashSrvOp::InstallCore
+                | ClashSrvOp::UpgradeCore
+                | ClashSrvOp::UpgradeDashboard => {},
 
                 ClashSrvOp::StartClashService => {
                     std::thread::sleep(std::time::Duration::from_secs(2));      // Waiting for mihomo to finish starting.
@@ -152,7 +441,24 @@ impl super::TabEvent for ClashSrvCtlTab {
             f.render_widget(ratatui::widgets::Clear, select_area);
             self.mode_selector.draw(f, select_area, true);
         }
+        if self.stack_selector.is_visible() {
+            let select_area = tools::centered_percent_rect(60, 30, f.size());
+            f.render_widget(ratatui::widgets::Clear, select_area);
+            self.stack_selector.draw(f, select_area, true);
+        }
+        let input_area = ratatui::prelude::Layout::default()
+            .constraints([
+                ratatui::prelude::Constraint::Percentage(25),
+                ratatui::prelude::Constraint::Length(8),
+                ratatui::prelude::Constraint::Min(0),
+            ])
+            .horizontal_margin(10)
+            .vertical_margin(1)
+            .split(f.size())[1];
+        self.dns_popup.draw(f, input_area);
+        self.config_popup.draw(f);
         self.msgpopup.draw(f, area);
+        self.confirm_popup.draw(f, area);
     }
 }
 
@@ -0,0 +1,316 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::msgpopup_methods;
+use crate::tui::{
+    symbols::CONNECTION,
+    utils::Keys,
+    widgets::{ConfirmPopup, InputPopup, List, MsgPopup},
+    EventState, Theme, Visibility,
+};
+use crate::utils::{str_bytes, SharedClashTuiUtil};
+use api::{ClashConnection, ClashConnections};
+crate::define_enum!(CnOp, [Close, CloseAll]); // CnOp: ConnectionTabOperation
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Visibility)]
+pub struct ConnectionTab {
+    is_visible: bool,
+
+    conn_list: List,
+    msgpopup: MsgPopup,
+    confirm_popup: ConfirmPopup,
+    filter_input: InputPopup,
+
+    conns: ClashConnections,
+    // connections shown in `conn_list`, in the same order
+    shown: Vec<ClashConnection>,
+    filter: String,
+    fetch_err: Option<String>,
+
+    last_fetch: Option<Instant>,
+    fetching: bool,
+    conn_tx: Sender<Result<ClashConnections, String>>,
+    conn_rx: Receiver<Result<ClashConnections, String>>,
+
+    clashtui_util: SharedClashTuiUtil,
+    op: Option<CnOp>,
+    confirm_op: Option<CnOp>,
+}
+
+impl ConnectionTab {
+    pub fn new(clashtui_util: SharedClashTuiUtil) -> Self {
+        let (conn_tx, conn_rx) = mpsc::channel();
+        Self {
+            is_visible: false,
+            conn_list: List::new(CONNECTION.to_string()),
+            msgpopup: Default::default(),
+            confirm_popup: ConfirmPopup::new(),
+            filter_input: InputPopup::new("Filter".to_string()),
+
+            conns: Default::default(),
+            shown: vec![],
+            filter: String::new(),
+            fetch_err: None,
+
+            last_fetch: None,
+            fetching: false,
+            conn_tx,
+            conn_rx,
+
+            clashtui_util,
+            op: None,
+            confirm_op: None,
+        }
+    }
+
+    /// Only poll the core while the tab is shown
+    fn refresh_if_due(&mut self) {
+        if !self.is_visible || self.fetching {
+            return;
+        }
+        if self
+            .last_fetch
+            .is_some_and(|t| t.elapsed() < REFRESH_INTERVAL)
+        {
+            return;
+        }
+        self.fetching = true;
+        self.last_fetch = Some(Instant::now());
+        self.clashtui_util.fetch_connections(self.conn_tx.clone());
+    }
+    fn refresh_now(&mut self) {
+        self.last_fetch = None;
+        self.refresh_if_due();
+    }
+
+    fn recv_connections(&mut self) {
+        while let Ok(res) = self.conn_rx.try_recv() {
+            self.fetching = false;
+            match res {
+                Ok(mut conns) => {
+                    // newest first
+                    conns
+                        .connections
+                        .sort_by(|a, b| b.start.cmp(&a.start));
+                    self.conns = conns;
+                    self.fetch_err = None;
+                }
+                Err(e) => {
+                    log::warn!("Fetch Connections:{e}");
+                    self.conns = Default::default();
+                    self.fetch_err = Some(e);
+                }
+            }
+            self.update_conn_list();
+        }
+    }
+
+    fn matches(&self, conn: &ClashConnection) -> bool {
+        if self.filter.is_empty() {
+            return true;
+        }
+        let filter = self.filter.to_lowercase();
+        [
+            conn.destination().as_str(),
+            conn.metadata.process_path.as_str(),
+            conn.rule.as_str(),
+            conn.rule_payload.as_str(),
+        ]
+        .into_iter()
+        .chain(conn.chains.iter().map(|c| c.as_str()))
+        .any(|s| s.to_lowercase().contains(&filter))
+    }
+
+    fn update_conn_list(&mut self) {
+        let cursor = self
+            .conn_list
+            .selected_index()
+            .and_then(|i| self.shown.get(i))
+            .map(|c| c.id.clone());
+        self.shown = self
+            .conns
+            .connections
+            .iter()
+            .filter(|c| self.matches(c))
+            .cloned()
+            .collect();
+
+        let fg = Theme::get().connection_info_fg;
+        let extras: Vec<(String, ratatui::style::Color)> = self
+            .shown
+            .iter()
+            .map(|c| {
+                let rule = if c.rule_payload.is_empty() {
+                    c.rule.clone()
+                } else {
+                    format!("{}({})", c.rule, c.rule_payload)
+                };
+                let chain: Vec<&str> = c.chains.iter().rev().map(|s| s.as_str()).collect();
+                (
+                    format!(
+                        "{} {} {} ↑{} ↓{} {}",
+                        c.metadata.network,
+                        rule,
+                        chain.join(" > "),
+                        str_bytes(c.upload),
+                        str_bytes(c.download),
+                        c.metadata.process_path,
+                    ),
+                    fg,
+                )
+            })
+            .collect();
+        self.conn_list
+            .set_items(self.shown.iter().map(|c| c.destination()).collect());
+        self.conn_list.set_colored_extras(extras.into_iter());
+        // follow the connection under cursor
+        if let Some(index) = cursor.and_then(|id| self.shown.iter().position(|c| c.id == id)) {
+            self.conn_list.select_index(index);
+        }
+    }
+
+    fn handle_close_ev(&mut self) {
+        let Some(conn) = self
+            .conn_list
+            .selected_index()
+            .and_then(|i| self.shown.get(i))
+        else {
+            return;
+        };
+        if let Err(err) = self.clashtui_util.close_connection(&conn.id) {
+            log::error!("Close `{}` => {err}", conn.destination());
+            self.popup_txt_msg(format!("Failed to Close: {err}"));
+        }
+        self.refresh_now();
+    }
+    fn handle_close_all_ev(&mut self) {
+        if let Err(err) = self.clashtui_util.close_all_connections() {
+            log::error!("Close all connections => {err}");
+            self.popup_txt_msg(format!("Failed to Close: {err}"));
+        }
+        self.refresh_now();
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = match self.fetch_err.as_ref() {
+            Some(e) => format!("Error: {e}"),
+            None => format!(
+                "Total ↑{} ↓{}  Connections: {}/{}",
+                str_bytes(self.conns.upload_total),
+                str_bytes(self.conns.download_total),
+                self.shown.len(),
+                self.conns.connections.len(),
+            ),
+        };
+        if !self.filter.is_empty() {
+            summary.push_str(&format!("  Filter: {}", self.filter));
+        }
+        summary
+    }
+}
+
+use ui::event::{Event, KeyEventKind};
+impl super::TabEvent for ConnectionTab {
+    fn popup_event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = self.msgpopup.event(ev)?;
+        if event_state.is_notconsumed() {
+            event_state = match self.confirm_popup.event(ev)? {
+                EventState::Yes => {
+                    self.op = self.confirm_op.take();
+                    EventState::WorkDone
+                }
+                EventState::Cancel | EventState::WorkDone => EventState::WorkDone,
+                _ => EventState::NotConsumed,
+            };
+        }
+        if event_state.is_notconsumed() {
+            event_state = self.filter_input.event(ev)?;
+            // Enter keeps the input, Esc clears it
+            if event_state == EventState::WorkDone && !self.filter_input.is_visible() {
+                self.filter = self.filter_input.get_input_data();
+                self.update_conn_list();
+            }
+        }
+
+        Ok(event_state)
+    }
+
+    fn event(&mut self, ev: &Event) -> Result<EventState, std::io::Error> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = EventState::NotConsumed;
+        if let Event::Key(key) = ev {
+            if key.kind != KeyEventKind::Press {
+                return Ok(EventState::NotConsumed);
+            }
+
+            event_state = match key.code.into() {
+                Keys::Search => {
+                    self.filter_input.set_pre_data(self.filter.clone());
+                    self.filter_input.show();
+                    EventState::WorkDone
+                }
+                Keys::ConnClose => {
+                    if self.conn_list.selected_index().is_some() {
+                        self.popup_txt_msg("Closing...".to_string());
+                        self.op.replace(CnOp::Close);
+                    }
+                    EventState::WorkDone
+                }
+                Keys::ConnCloseAll => {
+                    self.confirm_popup
+                        .popup_msg("`y` to Close all connections, `Esc` to cancel".to_string());
+                    self.confirm_op.replace(CnOp::CloseAll);
+                    EventState::WorkDone
+                }
+                _ => EventState::NotConsumed,
+            };
+
+            if event_state == EventState::NotConsumed {
+                event_state = self.conn_list.event(ev)?;
+            }
+        }
+
+        Ok(event_state)
+    }
+    fn late_event(&mut self) {
+        self.recv_connections();
+        if let Some(op) = self.op.take() {
+            self.hide_msgpopup();
+            match op {
+                CnOp::Close => self.handle_close_ev(),
+                CnOp::CloseAll => self.handle_close_all_ev(),
+            }
+        }
+        self.refresh_if_due();
+    }
+    fn draw(&mut self, f: &mut ratatui::prelude::Frame, area: ratatui::prelude::Rect) {
+        if !self.is_visible() {
+            return;
+        }
+        use ratatui::prelude::{Constraint, Layout};
+        use ratatui::widgets::{Block, Borders, Paragraph};
+
+        let chunks = Layout::default()
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(area);
+        f.render_widget(
+            Paragraph::new(self.summary()).block(Block::default().borders(Borders::ALL)),
+            chunks[0],
+        );
+        self.conn_list.draw(f, chunks[1], true);
+
+        self.filter_input.draw(f, chunks[0], true);
+        self.msgpopup.draw(f, area);
+        self.confirm_popup.draw(f, area);
+    }
+}
+msgpopup_methods!(ConnectionTab, txt);
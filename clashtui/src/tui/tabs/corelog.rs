@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+
+use ratatui::style::Color;
+
+use crate::msgpopup_methods;
+use crate::tui::{
+    symbols::CORE_LOG,
+    utils::Keys,
+    widgets::{InputPopup, List, MsgPopup},
+    EventState, Theme, Visibility,
+};
+use crate::utils::{CoreLogs, SharedClashTuiUtil};
+use api::LogLevel;
+
+// Oldest lines are dropped
+const MAX_LINES: usize = 5000;
+
+#[derive(Clone)]
+struct LogLine {
+    level: LogLevel,
+    time: String,
+    payload: String,
+}
+impl LogLine {
+    fn new(level: LogLevel, payload: String) -> Self {
+        Self {
+            level,
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+            payload,
+        }
+    }
+}
+
+/// Logs of mihomo, streamed from `/logs`
+#[derive(Visibility)]
+pub struct CoreLogTab {
+    is_visible: bool,
+
+    log_list: List,
+    msgpopup: MsgPopup,
+    search_input: InputPopup,
+
+    lines: VecDeque<LogLine>,
+    // lines shown in `log_list`, kept as is while paused
+    shown: Vec<LogLine>,
+    level: LogLevel,
+    search: String,
+    paused: bool,
+    connected: bool,
+
+    // followed once the tab is first shown
+    log_rx: Option<CoreLogs>,
+    clashtui_util: SharedClashTuiUtil,
+}
+
+impl CoreLogTab {
+    pub fn new(clashtui_util: SharedClashTuiUtil) -> Self {
+        Self {
+            is_visible: false,
+            log_list: List::new(CORE_LOG.to_string()),
+            msgpopup: Default::default(),
+            search_input: InputPopup::new("Search".to_string()),
+
+            lines: VecDeque::with_capacity(MAX_LINES),
+            shown: vec![],
+            level: LogLevel::default(),
+            search: String::new(),
+            paused: false,
+            connected: false,
+
+            log_rx: None,
+            clashtui_util,
+        }
+    }
+
+    fn push_line(&mut self, line: LogLine) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    fn recv_logs(&mut self) {
+        let mut updated = false;
+        while let Some(Ok(log)) = self.log_rx.as_ref().map(|rx| rx.try_recv()) {
+            match log {
+                Some(log) => {
+                    self.connected = true;
+                    self.push_line(LogLine::new(log.level, log.payload));
+                }
+                // the core is retried every few seconds, only tell once
+                None if self.connected => {
+                    self.connected = false;
+                    self.push_line(LogLine::new(
+                        LogLevel::Error,
+                        "Lost connection to the core, reconnecting".to_string(),
+                    ));
+                }
+                None => continue,
+            }
+            updated = true;
+        }
+        if updated && !self.paused {
+            self.update_log_list();
+        }
+    }
+
+    /// Follow the latest lines unless paused
+    fn update_log_list(&mut self) {
+        let search = self.search.to_lowercase();
+        self.shown = self
+            .lines
+            .iter()
+            .filter(|l| l.level <= self.level)
+            .filter(|l| search.is_empty() || l.payload.to_lowercase().contains(&search))
+            .cloned()
+            .collect();
+
+        let theme = Theme::get();
+        let extras: Vec<(String, Color)> = self
+            .shown
+            .iter()
+            .map(|l| {
+                let fg = match l.level {
+                    LogLevel::Debug => theme.log_debug_fg,
+                    LogLevel::Info | LogLevel::Silent => theme.log_info_fg,
+                    LogLevel::Warning => theme.log_warning_fg,
+                    LogLevel::Error => theme.log_error_fg,
+                };
+                (format!("[{}] {}", l.level, l.payload), fg)
+            })
+            .collect();
+        self.log_list
+            .set_items(self.shown.iter().map(|l| l.time.clone()).collect());
+        self.log_list.set_colored_extras(extras.into_iter());
+        if !self.paused {
+            if let Some(last) = self.shown.len().checked_sub(1) {
+                self.log_list.select_index(last);
+            }
+        }
+    }
+
+    fn switch_level(&mut self) {
+        self.level = match self.level {
+            LogLevel::Debug => LogLevel::Info,
+            LogLevel::Info => LogLevel::Warning,
+            LogLevel::Warning => LogLevel::Error,
+            LogLevel::Error | LogLevel::Silent => LogLevel::Debug,
+        };
+        // the old stream stops once its receiver is dropped
+        self.log_rx = Some(self.clashtui_util.follow_core_logs(self.level));
+        self.update_log_list();
+    }
+
+    fn handle_save_ev(&mut self) {
+        let lines: Vec<String> = self
+            .shown
+            .iter()
+            .map(|l| format!("{} [{}] {}", l.time, l.level, l.payload))
+            .collect();
+        match self.clashtui_util.save_core_logs(&lines) {
+            Ok(path) => self.popup_txt_msg(format!("Saved to {}", path.display())),
+            Err(err) => {
+                log::error!("Save core logs => {err}");
+                self.popup_txt_msg(format!("Failed to Save: {err}"));
+            }
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!(
+            "Level: {}  Lines: {}/{}",
+            self.level,
+            self.shown.len(),
+            self.lines.len()
+        );
+        if self.paused {
+            summary.push_str("  [Paused]");
+        }
+        if !self.search.is_empty() {
+            summary.push_str(&format!("  Search: {}", self.search));
+        }
+        summary
+    }
+}
+
+use ui::event::{Event, KeyEventKind};
+impl super::TabEvent for CoreLogTab {
+    fn popup_event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = self.msgpopup.event(ev)?;
+        if event_state.is_notconsumed() {
+            event_state = self.search_input.event(ev)?;
+            // Enter keeps the input, Esc clears it
+            if event_state == EventState::WorkDone && !self.search_input.is_visible() {
+                self.search = self.search_input.get_input_data();
+                self.update_log_list();
+            }
+        }
+
+        Ok(event_state)
+    }
+
+    fn event(&mut self, ev: &Event) -> Result<EventState, std::io::Error> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = EventState::NotConsumed;
+        if let Event::Key(key) = ev {
+            if key.kind != KeyEventKind::Press {
+                return Ok(EventState::NotConsumed);
+            }
+
+            event_state = match key.code.into() {
+                Keys::Search => {
+                    self.search_input.set_pre_data(self.search.clone());
+                    self.search_input.show();
+                    EventState::WorkDone
+                }
+                Keys::LogLevel => {
+                    self.switch_level();
+                    EventState::WorkDone
+                }
+                Keys::LogPause => {
+                    self.paused = !self.paused;
+                    if !self.paused {
+                        self.update_log_list();
+                    }
+                    EventState::WorkDone
+                }
+                Keys::Save => {
+                    self.handle_save_ev();
+                    EventState::WorkDone
+                }
+                _ => EventState::NotConsumed,
+            };
+
+            if event_state == EventState::NotConsumed {
+                event_state = self.log_list.event(ev)?;
+            }
+        }
+
+        Ok(event_state)
+    }
+    fn late_event(&mut self) {
+        if self.is_visible && self.log_rx.is_none() {
+            self.log_rx = Some(self.clashtui_util.follow_core_logs(self.level));
+        }
+        self.recv_logs();
+    }
+    fn draw(&mut self, f: &mut ratatui::prelude::Frame, area: ratatui::prelude::Rect) {
+        if !self.is_visible() {
+            return;
+        }
+        use ratatui::prelude::{Constraint, Layout};
+        use ratatui::widgets::{Block, Borders, Paragraph};
+
+        let chunks = Layout::default()
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(area);
+        f.render_widget(
+            Paragraph::new(self.summary()).block(Block::default().borders(Borders::ALL)),
+            chunks[0],
+        );
+        self.log_list.draw(f, chunks[1], true);
+
+        self.search_input.draw(f, chunks[0], true);
+        self.msgpopup.draw(f, area);
+    }
+}
+msgpopup_methods!(CoreLogTab, txt);
@@ -0,0 +1,129 @@
+use ratatui::{prelude as Ra, widgets as Raw};
+use ui::event::{Event, KeyEventKind};
+
+use crate::tui::{utils::Keys, widgets::InputPopup, EventState, Visibility};
+
+const DEFAULT_RECORD_TYPE: &str = "A";
+
+#[derive(PartialEq)]
+enum Fouce {
+    Name,
+    Type,
+}
+
+pub struct DnsQueryPopup {
+    pub name_input: InputPopup,
+    pub type_input: InputPopup,
+    fouce: Fouce,
+}
+
+impl DnsQueryPopup {
+    pub fn new() -> Self {
+        Self {
+            name_input: InputPopup::new("Domain".to_string()),
+            type_input: InputPopup::new("Type (A, AAAA, CNAME, ...)".to_string()),
+            fouce: Fouce::Name,
+        }
+    }
+
+    pub fn event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible() {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = EventState::NotConsumed;
+        if let Event::Key(key) = ev {
+            if key.kind == KeyEventKind::Press {
+                event_state = match key.code.into() {
+                    Keys::Tab => {
+                        self.switch_fouce();
+                        EventState::WorkDone
+                    }
+                    Keys::Select => {
+                        self.name_input.handle_enter_ev();
+                        self.type_input.handle_enter_ev();
+                        self.hide();
+
+                        EventState::WorkDone
+                    }
+                    Keys::Esc => {
+                        self.name_input.handle_esc_ev();
+                        self.type_input.handle_esc_ev();
+                        self.hide();
+
+                        EventState::WorkDone
+                    }
+                    _ => match self.fouce {
+                        Fouce::Name => self.name_input.event(ev)?,
+                        Fouce::Type => self.type_input.event(ev)?,
+                    },
+                };
+            }
+        }
+
+        Ok(event_state)
+    }
+
+    pub fn draw(&mut self, f: &mut Ra::Frame, area: Ra::Rect) {
+        if !self.is_visible() {
+            return;
+        }
+
+        let chunks = Ra::Layout::default()
+            .constraints([
+                Ra::Constraint::Percentage(50),
+                Ra::Constraint::Percentage(50),
+            ])
+            .margin(1)
+            .split(area);
+
+        f.render_widget(Raw::Clear, area);
+        let sel = self.fouce == Fouce::Name;
+        self.name_input.draw(f, chunks[0], sel);
+        self.type_input.draw(f, chunks[1], !sel);
+
+        let block = Raw::Block::new()
+            .borders(Raw::Borders::ALL)
+            .border_style(Ra::Style::default().fg(Ra::Color::Rgb(135, 206, 236)))
+            .title("DnsQuery");
+        f.render_widget(block, area);
+    }
+
+    /// (domain, record type), empty domain means canceled
+    pub fn get_query(&self) -> (String, String) {
+        let record_type = self.type_input.get_input_data();
+        (
+            self.name_input.get_input_data().trim().to_string(),
+            if record_type.trim().is_empty() {
+                DEFAULT_RECORD_TYPE.to_string()
+            } else {
+                record_type.trim().to_uppercase()
+            },
+        )
+    }
+
+    pub fn switch_fouce(&mut self) {
+        if self.fouce == Fouce::Name {
+            self.fouce = Fouce::Type;
+        } else {
+            self.fouce = Fouce::Name;
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.name_input.is_visible() && self.type_input.is_visible()
+    }
+    pub fn show(&mut self) {
+        self.fouce = Fouce::Name;
+        // keep the last query for editing
+        let (name, record_type) = self.get_query();
+        self.name_input.set_pre_data(name);
+        self.type_input.set_pre_data(record_type);
+        self.name_input.show();
+        self.type_input.show();
+    }
+    pub fn hide(&mut self) {
+        self.name_input.hide();
+        self.type_input.hide();
+    }
+}
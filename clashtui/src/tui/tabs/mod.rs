@@ -1,21 +1,44 @@
 mod clashsrvctl;
+mod connection;
+mod corelog;
+mod dns_input;
 mod profile;
 mod profile_input;
+mod provider;
+mod proxy;
+mod rule;
+mod runtime_config;
 
 pub use clashsrvctl::ClashSrvCtlTab;
+pub use connection::ConnectionTab;
+pub use corelog::CoreLogTab;
 pub use profile::ProfileTab;
+pub use provider::ProviderTab;
+pub use proxy::ProxyTab;
+pub use rule::RuleTab;
 
 pub enum Tabs {
     Profile(ProfileTab),
     ClashSrvCtl(ClashSrvCtlTab),
+    Proxy(ProxyTab),
+    Connection(ConnectionTab),
+    Rule(RuleTab),
+    Provider(ProviderTab),
+    CoreLog(CoreLogTab),
 }
-impl ToString for Tabs {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Tabs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use super::symbols;
-        match self {
-            Tabs::Profile(_) => symbols::PROFILE.to_string(),
-            Tabs::ClashSrvCtl(_) => symbols::CLASHSRVCTL.to_string(),
-        }
+        let x = match self {
+            Tabs::Profile(_) => symbols::PROFILE,
+            Tabs::ClashSrvCtl(_) => symbols::CLASHSRVCTL,
+            Tabs::Proxy(_) => symbols::PROXY,
+            Tabs::Connection(_) => symbols::CONNECTION,
+            Tabs::Rule(_) => symbols::RULE,
+            Tabs::Provider(_) => symbols::PROVIDER,
+            Tabs::CoreLog(_) => symbols::CORE_LOG,
+        };
+        write!(f, "{}", x)
     }
 }
 impl std::cmp::PartialEq<std::string::String> for Tabs {
@@ -26,28 +49,16 @@ impl std::cmp::PartialEq<std::string::String> for Tabs {
 }
 pub trait TabEvent {
     fn draw(&mut self, f: &mut ratatui::prelude::Frame, area: ratatui::prelude::Rect);
-    fn popup_event(
-        &mut self,
-        ev: &ui::event::Event,
-    ) -> Result<ui::EventState, impl std::error::Error>;
-    fn event(
-        &mut self,
-        ev: &ui::event::Event,
-    ) -> Result<ui::EventState, impl std::error::Error>;
+    fn popup_event(&mut self, ev: &ui::event::Event) -> Result<ui::EventState, ui::Infailable>;
+    fn event(&mut self, ev: &ui::event::Event) -> Result<ui::EventState, std::io::Error>;
     fn late_event(&mut self);
 }
 
 #[macro_export]
 macro_rules! msgpopup_methods {
     ($type:ident) => {
+        $crate::msgpopup_methods!($type, txt);
         impl $type {
-            // single-line popup
-            pub fn popup_txt_msg(&mut self, msg: String) {
-                if ! msg.is_empty() {
-                    self.msgpopup.push_txt_msg(msg);
-                    self.msgpopup.show();
-                }
-            }
             // multi-lines popup
             pub fn popup_list_msg<I>(&mut self, msg: I)
             where
@@ -62,6 +73,18 @@ macro_rules! msgpopup_methods {
                     self.msgpopup.show();
                 }
             }
+        }
+    };
+    // for those without multi-lines messages
+    ($type:ident, txt) => {
+        impl $type {
+            // single-line popup
+            pub fn popup_txt_msg(&mut self, msg: String) {
+                if ! msg.is_empty() {
+                    self.msgpopup.push_txt_msg(msg);
+                    self.msgpopup.show();
+                }
+            }
             #[allow(unused)]
             pub fn hide_msgpopup(&mut self) {
                 self.msgpopup.hide();
@@ -105,6 +128,20 @@ define_enum!(
         StartClashService,
         StopClashService,
         SetPermission,
-        SwitchMode
+        SwitchMode,
+        SwitchTun,
+        SwitchTunStack,
+        RuntimeConfig,
+        SaveRuntimeConfig,
+        DnsQuery,
+        FlushFakeIp,
+        FlushDnsCache,
+        GeoDataStatus,
+        UpdateGeoDataByCore,
+        UpdateGeoDataFromGithub,
+        UpgradeCore,
+        UpgradeDashboard,
+        InstallCore,
+        RollbackCore
     ]
 );
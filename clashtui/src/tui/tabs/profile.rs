@@ -3,11 +3,13 @@ use crate::tui::{
     symbols::{PROFILE, TEMPALTE},
     utils::Keys,
     widgets::{ConfirmPopup, List, MsgPopup},
-    EventState, Visibility,
+    EventState, Theme, Visibility,
 };
-use crate::utils::{self, SharedClashTuiState, SharedClashTuiUtil, ProfileType};
+use crate::utils::{self, ProfileType, ProfileUpdate, SharedClashTuiState, SharedClashTuiUtil};
 use crate::{msgpopup_methods, utils::get_mtime};
-crate::define_enum!(PTOp, [Update, UpdateAll, Select, Delete]);   // PTOp: ProfileTabOperation
+crate::define_enum!(PTOp, [Update, UpdateAll, UpdateDue, CancelUpdate, Select, Delete]);   // PTOp: ProfileTabOperation
+// How often to look for profiles due for an update
+const DUE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
 
 #[derive(PartialEq)]
 enum Fouce {
@@ -30,6 +32,15 @@ pub struct ProfileTab {
     clashtui_state: SharedClashTuiState,
     op: Option<PTOp>,
     confirm_op: Option<PTOp>,
+    due_checked_at: Option<std::time::Instant>,
+
+    // Updates run one by one in background
+    update: Option<ProfileUpdate>,
+    update_queue: std::collections::VecDeque<(String, bool)>,
+    update_report: Vec<String>,
+    show_progress: bool,
+    // auto updates only log their report
+    show_report: bool,
 }
 
 impl ProfileTab {
@@ -50,6 +61,13 @@ impl ProfileTab {
             clashtui_state,
             op: None,
             confirm_op: None,
+            due_checked_at: None,
+
+            update: None,
+            update_queue: Default::default(),
+            update_report: Vec::new(),
+            show_progress: false,
+            show_report: false,
         };
 
         instance.update_profile_list();
@@ -61,6 +79,11 @@ impl ProfileTab {
             .get_template_names()
             .expect("Unable to init ProfileTab");
         instance.template_list.set_items(template_names);
+        // subscriptions lapse silently otherwise
+        let warnings = instance.clashtui_util.subscription_warnings();
+        if !warnings.is_empty() {
+            instance.popup_list_msg(warnings);
+        }
 
         instance
     }
@@ -80,33 +103,134 @@ impl ProfileTab {
         };
     }
     fn handle_update_profile_ev(&mut self, does_update_all: bool) {
-        if let Some(profile_name) = self.profile_list.selected() {
-            match self
-                .clashtui_util
-                .update_profile(profile_name, does_update_all)
-            {
-                Ok(mut msg) => {
-                    if profile_name == self.clashtui_state.borrow().get_profile() {
-                        if let Err(err) = self.clashtui_util.select_profile(profile_name) {
-                            log::error!("{profile_name} => {err:?}");
-                            msg.push(err.to_string());
-                        } else {
-                            msg.push("Update and selected".to_string());
-                        }
-                    } else {
-                        msg.push("Update success".to_string());
-                    }
-
-                    self.popup_list_msg(msg);
-                }
+        if let Some(profile_name) = self.profile_list.selected().cloned() {
+            self.queue_update(profile_name, does_update_all);
+            self.show_progress = true;
+            self.show_report = true;
+            self.show_update_progress();
+        }
+    }
+    /// Update the profiles due for it, see [is_profile_due][crate::utils::ClashTuiUtil::is_profile_due]
+    fn handle_update_due_profiles_ev(&mut self) {
+        self.due_checked_at = Some(std::time::Instant::now());
+        for profile_name in self.clashtui_util.due_profiles() {
+            log::info!("Auto update `{profile_name}`");
+            self.queue_update(profile_name, false);
+        }
+    }
+    fn handle_cancel_update_ev(&mut self) {
+        self.update_queue.clear();
+        if let Some(update) = self.update.as_ref() {
+            update.cancel();
+            // see how it stops
+            self.show_progress = true;
+            self.show_report = true;
+            self.show_update_progress();
+        }
+    }
+    fn queue_update(&mut self, profile_name: String, does_update_all: bool) {
+        let is_queued = self
+            .update
+            .as_ref()
+            .is_some_and(|u| u.profile_name == profile_name)
+            || self.update_queue.iter().any(|(p, _)| *p == profile_name);
+        if !is_queued {
+            self.update_queue.push_back((profile_name, does_update_all));
+        }
+        self.start_next_update();
+    }
+    fn start_next_update(&mut self) {
+        while self.update.is_none() {
+            let Some((profile_name, does_update_all)) = self.update_queue.pop_front() else {
+                break;
+            };
+            match self.clashtui_util.start_update(&profile_name, does_update_all) {
+                Ok(update) => self.update = Some(update),
                 Err(err) => {
                     log::error!("{profile_name} => {err:?}");
-                    self.popup_txt_msg(format!("Failed to Update: {err}"));
+                    self.update_report
+                        .push(format!("Failed to Update `{profile_name}`: {err}"));
                 }
-            };
-            self.update_profile_list();
+            }
+        }
+    }
+    /// Receive the progress of the running update, the report is shown once all are done
+    ///
+    /// Auto updates alone only log it, not to interrupt the user.
+    fn poll_update(&mut self) {
+        let Some(update) = self.update.as_mut() else {
+            return;
+        };
+        let Some(outcome) = update.poll() else {
+            self.show_update_progress();
+            return;
+        };
+        let profile_name = update.profile_name.clone();
+        self.update = None;
+        self.update_report.push(format!("Update `{profile_name}`:"));
+        match outcome {
+            Ok(outcome) => {
+                self.update_report
+                    .extend(self.clashtui_util.finish_update(&profile_name, outcome));
+                if profile_name == *self.clashtui_state.borrow().get_profile() {
+                    if let Err(err) = self.clashtui_util.select_profile(&profile_name) {
+                        log::error!("{profile_name} => {err:?}");
+                        self.update_report.push(err.to_string());
+                    } else {
+                        self.update_report.push("Update and selected".to_string());
+                    }
+                } else {
+                    self.update_report.push("Update success".to_string());
+                }
+            }
+            Err(err) => {
+                log::error!("{profile_name} => {err:?}");
+                self.update_report.push(format!("Failed to Update: {err}"));
+            }
+        }
+        self.update_profile_list();
+        self.start_next_update();
+        if self.update.is_none() {
+            // replace the progress, or add to what's shown
+            if std::mem::take(&mut self.show_progress) {
+                self.hide_msgpopup();
+            }
+            let report = std::mem::take(&mut self.update_report);
+            if std::mem::take(&mut self.show_report) {
+                self.popup_list_msg(report);
+            } else {
+                report.iter().for_each(|line| log::info!("{line}"));
+            }
+        } else {
+            self.show_update_progress();
         }
     }
+    fn show_update_progress(&mut self) {
+        if !self.show_progress {
+            return;
+        }
+        let Some(update) = self.update.as_ref() else {
+            return;
+        };
+        let title = if update.is_cancelled() {
+            format!("Cancelling `{}`...", update.profile_name)
+        } else {
+            format!(
+                "Updating `{}`..., `Esc` to hide or cancel",
+                update.profile_name
+            )
+        };
+        let mut msg = self.update_report.clone();
+        msg.push(title);
+        msg.extend(update.progress());
+        msg.extend(
+            self.update_queue
+                .iter()
+                .map(|(p, _)| format!("`{p}`: queued")),
+        );
+        self.msgpopup.set_msg(msg);
+        self.msgpopup.show();
+    }
     fn handle_delete_profile_ev(&mut self) {
         if let Some(profile_name) = self.profile_list.selected() {
             if let Err(e) = self.clashtui_util.rmf_profile(profile_name) {
@@ -150,26 +274,44 @@ impl ProfileTab {
             })
             .collect();
         let now = std::time::SystemTime::now();
-        // They can view the update interval themselves; there's no need to remind.
-        //if profile_times.iter().filter_map(|t| t.as_ref()).any(|t| {
-        //    // Within one day
-        //    *t < now - std::time::Duration::from_secs(24 * 60 * 60)
-        //}) {
-        //    self.popup_txt_msg(
-        //        "Some profile might haven't updated for more than one day".to_string(),
-        //    )
-        //};
+        let timestamp = chrono::Utc::now().timestamp();
+        let subscriptions: Vec<Option<String>> = profile_names
+            .iter()
+            .map(|v| {
+                self.clashtui_util
+                    .subscription_info(v)
+                    .map(|info| utils::str_subscription(&info, timestamp))
+            })
+            .collect();
+        // Overdue ones are marked, they will be updated soon
+        let theme = Theme::get();
+        let colors: Vec<_> = profile_names
+            .iter()
+            .map(|v| {
+                if self.clashtui_util.is_profile_due(v, timestamp) {
+                    theme.profile_update_interval_fg
+                } else {
+                    theme.profile_info_fg
+                }
+            })
+            .collect();
         self.profile_list.set_items(profile_names);
-        self.profile_list
-            .set_extras(profile_times.into_iter().map(|t| {
-                t.map(|t| {
+        self.profile_list.set_colored_extras(
+            profile_times.into_iter().zip(subscriptions).zip(colors).map(|((t, sub), color)| {
+                let age = t.map(|t| {
                     utils::str_duration(
                         now.duration_since(t)
                             .expect("Clock may have gone backwards"),
                     )
                 })
-                .unwrap_or("Never/Err".to_string())
-            }))
+                .unwrap_or("Never/Err".to_string());
+                let extra = match sub {
+                    Some(sub) => format!("{age}  {sub}"),
+                    None => age,
+                };
+                (extra, color)
+            }),
+        )
     }
 }
 use ui::event::{Event, KeyEventKind};
@@ -179,6 +321,21 @@ impl super::TabEvent for ProfileTab {
             return Ok(EventState::NotConsumed);
         }
 
+        // the progress is shown, but the update goes on
+        if self.show_progress && self.update.is_some() && self.msgpopup.is_visible() {
+            if let Event::Key(key) = ev {
+                if key.kind == KeyEventKind::Press && &Keys::Esc == key {
+                    self.show_progress = false;
+                    self.hide_msgpopup();
+                    self.confirm_popup.popup_msg(
+                        "`y` to cancel updating, `Esc` to keep it in background".to_string(),
+                    );
+                    self.confirm_op.replace(PTOp::CancelUpdate);
+                    return Ok(EventState::WorkDone);
+                }
+            }
+        }
+
         let mut event_state = self.msgpopup.event(ev)?;
         if event_state.is_notconsumed() {
             event_state = match self.confirm_popup.event(ev)? {
@@ -233,12 +390,10 @@ impl super::TabEvent for ProfileTab {
                             EventState::WorkDone
                         }
                         Keys::ProfileUpdate => {
-                            self.popup_txt_msg("Updating...".to_string());
                             self.op.replace(PTOp::Update);
                             EventState::WorkDone
                         }
                         Keys::ProfileUpdateAll => {
-                            self.popup_txt_msg("Updating...".to_string());
                             self.op.replace(PTOp::UpdateAll);
                             EventState::WorkDone
                         }
@@ -384,11 +539,24 @@ impl super::TabEvent for ProfileTab {
         Ok(event_state)
     }
     fn late_event(&mut self) {
+        self.poll_update();
+        if self.op.is_none()
+            && self
+                .due_checked_at
+                .is_none_or(|t| t.elapsed() >= DUE_CHECK_INTERVAL)
+        {
+            self.op.replace(PTOp::UpdateDue);
+        }
         if let Some(op) = self.op.take() {
-            self.hide_msgpopup();
+            // they run on their own, keep whatever is shown
+            if !matches!(op, PTOp::UpdateDue | PTOp::CancelUpdate) {
+                self.hide_msgpopup();
+            }
             match op {
                 PTOp::Update => self.handle_update_profile_ev(false),
                 PTOp::UpdateAll => self.handle_update_profile_ev(true),
+                PTOp::UpdateDue => self.handle_update_due_profiles_ev(),
+                PTOp::CancelUpdate => self.handle_cancel_update_ev(),
                 PTOp::Select => self.handle_select_profile_ev(),
                 PTOp::Delete => self.handle_delete_profile_ev(),
             }
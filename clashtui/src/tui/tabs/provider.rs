@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use ratatui::style::Color;
+
+use crate::msgpopup_methods;
+use crate::tui::{
+    symbols::PROVIDER,
+    utils::Keys,
+    widgets::{List, MsgPopup},
+    EventState, Theme, Visibility,
+};
+use crate::utils::{str_duration, ProviderOp, ProviderResult, SharedClashTuiUtil};
+use api::{ClashProvider, ProfileSectionType};
+crate::define_enum!(PvOp, [Refresh]); // PvOp: ProviderTabOperation
+
+enum Status {
+    Running(ProviderOp),
+    Done(ProviderOp, Result<(), String>),
+}
+
+#[derive(Visibility)]
+pub struct ProviderTab {
+    is_visible: bool,
+
+    provider_list: List,
+    msgpopup: MsgPopup,
+
+    providers: Vec<ClashProvider>,
+    status: HashMap<(ProfileSectionType, String), Status>,
+    result_tx: Sender<ProviderResult>,
+    result_rx: Receiver<ProviderResult>,
+    // fetched once the tab is first shown
+    loaded: bool,
+    provider_tx: Sender<Result<Vec<ClashProvider>, String>>,
+    provider_rx: Receiver<Result<Vec<ClashProvider>, String>>,
+
+    clashtui_util: SharedClashTuiUtil,
+    op: Option<PvOp>,
+}
+
+fn key_of(p: &ClashProvider) -> (ProfileSectionType, String) {
+    let t = if p.is_rule() {
+        ProfileSectionType::RuleProvider
+    } else {
+        ProfileSectionType::ProxyProvider
+    };
+    (t, p.name.clone())
+}
+
+impl ProviderTab {
+    pub fn new(clashtui_util: SharedClashTuiUtil) -> Self {
+        let (result_tx, result_rx) = mpsc::channel();
+        let (provider_tx, provider_rx) = mpsc::channel();
+        Self {
+            is_visible: false,
+            provider_list: List::new(PROVIDER.to_string()),
+            msgpopup: Default::default(),
+
+            providers: vec![],
+            status: HashMap::new(),
+            result_tx,
+            result_rx,
+            loaded: false,
+            provider_tx,
+            provider_rx,
+
+            clashtui_util,
+            op: None,
+        }
+    }
+
+    fn refresh_providers(&mut self) {
+        self.loaded = true;
+        self.clashtui_util.fetch_providers(self.provider_tx.clone());
+    }
+
+    fn recv_providers(&mut self) {
+        while let Ok(res) = self.provider_rx.try_recv() {
+            match res {
+                Ok(providers) => {
+                    self.providers = providers;
+                    self.update_provider_list();
+                }
+                Err(e) => {
+                    log::warn!("Fetch Providers:{e}");
+                    // The core might not be running yet, press `r` later
+                    if self.is_visible {
+                        self.popup_txt_msg(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_provider_list(&mut self) {
+        let theme = Theme::get();
+        let now = std::time::SystemTime::now();
+        let extras: Vec<(String, Color)> = self
+            .providers
+            .iter()
+            .map(|p| {
+                let unit = if p.is_rule() { "rules" } else { "nodes" };
+                let updated = p
+                    .updated_at()
+                    .and_then(|t| now.duration_since(t).ok())
+                    .map_or("Never".to_string(), str_duration);
+                let info = format!(
+                    "{} {} {} {}, {}",
+                    p.provider_type,
+                    p.vehicle_type,
+                    p.count(),
+                    unit,
+                    updated
+                );
+                match self.status.get(&key_of(p)) {
+                    None => (info, theme.provider_info_fg),
+                    Some(Status::Running(op)) => (
+                        format!("{info} [{}]", running_str(*op)),
+                        theme.provider_info_fg,
+                    ),
+                    Some(Status::Done(op, Ok(()))) => (
+                        format!("{info} [{} done]", op_str(*op)),
+                        theme.provider_ok_fg,
+                    ),
+                    Some(Status::Done(op, Err(e))) => (
+                        format!("{info} [{} failed: {e}]", op_str(*op)),
+                        theme.provider_failed_fg,
+                    ),
+                }
+            })
+            .collect();
+        self.provider_list
+            .set_items(self.providers.iter().map(|p| p.name.clone()).collect());
+        self.provider_list.set_colored_extras(extras.into_iter());
+    }
+
+    fn selected(&self) -> Option<&ClashProvider> {
+        self.provider_list
+            .selected_index()
+            .and_then(|i| self.providers.get(i))
+    }
+
+    fn start(&mut self, op: ProviderOp, targets: Vec<(ProfileSectionType, String)>) {
+        let targets: Vec<(ProfileSectionType, String)> = targets
+            .into_iter()
+            .filter(|k| !matches!(self.status.get(k), Some(Status::Running(_))))
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        for k in targets.iter() {
+            self.status.insert(k.clone(), Status::Running(op));
+        }
+        self.clashtui_util
+            .run_provider_op(op, targets, self.result_tx.clone());
+        self.update_provider_list();
+    }
+
+    fn handle_op_ev(&mut self, op: ProviderOp, all: bool) {
+        let targets = if all {
+            self.providers
+                .iter()
+                .filter(|p| match op {
+                    // the others are not fetched from network
+                    ProviderOp::Update => p.vehicle_type == "HTTP",
+                    ProviderOp::HealthCheck => !p.is_rule(),
+                })
+                .map(key_of)
+                .collect()
+        } else {
+            match self.selected() {
+                Some(p) if op == ProviderOp::HealthCheck && p.is_rule() => {
+                    self.popup_txt_msg("Only proxy providers can be checked".to_string());
+                    return;
+                }
+                Some(p) => vec![key_of(p)],
+                None => return,
+            }
+        };
+        self.start(op, targets);
+    }
+
+    /// Collect finished ops without blocking
+    fn recv_results(&mut self) {
+        let mut updated = false;
+        while let Ok((t, name, res)) = self.result_rx.try_recv() {
+            let key = (t, name);
+            if let Some(Status::Running(op)) = self.status.remove(&key) {
+                self.status.insert(key, Status::Done(op, res));
+            }
+            updated = true;
+        }
+        if !updated {
+            return;
+        }
+        // counts and update times changed
+        if !self
+            .status
+            .values()
+            .any(|s| matches!(s, Status::Running(_)))
+        {
+            self.refresh_providers();
+        }
+        self.update_provider_list();
+    }
+
+    fn handle_detail_ev(&mut self) {
+        let Some(p) = self.selected() else {
+            return;
+        };
+        let mut lines = vec![
+            format!("Name: {}", p.name),
+            format!("Type: {}", p.provider_type),
+            format!("Vehicle: {}", p.vehicle_type),
+        ];
+        if p.is_rule() {
+            lines.push(format!("Behavior: {}", p.behavior));
+            lines.push(format!("Rules: {}", p.rule_count));
+        } else {
+            lines.push(format!("Nodes: {}", p.proxies.len()));
+        }
+        lines.push(format!("Updated at: {}", p.updated_at));
+        match self.status.get(&key_of(p)) {
+            Some(Status::Running(op)) => lines.push(format!("Now: {}", running_str(*op))),
+            Some(Status::Done(op, Ok(()))) => lines.push(format!("Last {}: done", op_str(*op))),
+            Some(Status::Done(op, Err(e))) => {
+                lines.push(format!("Last {}: failed, {e}", op_str(*op)))
+            }
+            None => (),
+        }
+        if !p.is_rule() {
+            lines.push(String::new());
+            lines.extend(p.proxies.iter().map(|n| n.name.clone()));
+        }
+        self.popup_list_msg(lines);
+    }
+}
+
+fn op_str(op: ProviderOp) -> &'static str {
+    match op {
+        ProviderOp::Update => "update",
+        ProviderOp::HealthCheck => "health check",
+    }
+}
+fn running_str(op: ProviderOp) -> &'static str {
+    match op {
+        ProviderOp::Update => "Updating",
+        ProviderOp::HealthCheck => "Checking",
+    }
+}
+
+use ui::event::{Event, KeyEventKind};
+impl super::TabEvent for ProviderTab {
+    fn popup_event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        self.msgpopup.event(ev)
+    }
+
+    fn event(&mut self, ev: &Event) -> Result<EventState, std::io::Error> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = EventState::NotConsumed;
+        if let Event::Key(key) = ev {
+            if key.kind != KeyEventKind::Press {
+                return Ok(EventState::NotConsumed);
+            }
+
+            event_state = match key.code.into() {
+                Keys::Refresh => {
+                    self.popup_txt_msg("Refreshing...".to_string());
+                    self.op.replace(PvOp::Refresh);
+                    EventState::WorkDone
+                }
+                Keys::Select => {
+                    self.handle_detail_ev();
+                    EventState::WorkDone
+                }
+                Keys::ProfileUpdate => {
+                    self.handle_op_ev(ProviderOp::Update, false);
+                    EventState::WorkDone
+                }
+                Keys::ProfileUpdateAll => {
+                    self.handle_op_ev(ProviderOp::Update, true);
+                    EventState::WorkDone
+                }
+                Keys::ProxyDelayTest => {
+                    self.handle_op_ev(ProviderOp::HealthCheck, false);
+                    EventState::WorkDone
+                }
+                Keys::ProviderHealthCheckAll => {
+                    self.handle_op_ev(ProviderOp::HealthCheck, true);
+                    EventState::WorkDone
+                }
+                _ => EventState::NotConsumed,
+            };
+
+            if event_state == EventState::NotConsumed {
+                event_state = self.provider_list.event(ev)?;
+            }
+        }
+
+        Ok(event_state)
+    }
+    fn late_event(&mut self) {
+        // Results might arrive while the tab is hidden
+        self.recv_results();
+        self.recv_providers();
+        if self.is_visible && !self.loaded {
+            self.refresh_providers();
+        }
+        if let Some(op) = self.op.take() {
+            self.hide_msgpopup();
+            match op {
+                PvOp::Refresh => self.refresh_providers(),
+            }
+        }
+    }
+    fn draw(&mut self, f: &mut ratatui::prelude::Frame, area: ratatui::prelude::Rect) {
+        if !self.is_visible() {
+            return;
+        }
+
+        self.provider_list.draw(f, area, true);
+        self.msgpopup.draw(f, area);
+    }
+}
+msgpopup_methods!(ProviderTab);
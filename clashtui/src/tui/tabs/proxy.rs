@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use ratatui::style::Color;
+
+use crate::msgpopup_methods;
+use crate::tui::{
+    symbols::{PROXY, PROXY_MEMBER},
+    utils::Keys,
+    widgets::{List, MsgPopup},
+    EventState, Theme, Visibility,
+};
+use crate::utils::{DelayResult, SharedClashTuiUtil};
+use api::ClashProxies;
+crate::define_enum!(PxOp, [Refresh, Select]); // PxOp: ProxyTabOperation
+
+// ms, used to pick the color of a delay
+const DELAY_LOW: u64 = 300;
+const DELAY_MEDIUM: u64 = 800;
+
+#[derive(PartialEq)]
+enum Fouce {
+    Group,
+    Member,
+}
+
+#[derive(Visibility)]
+pub struct ProxyTab {
+    is_visible: bool,
+    fouce: Fouce,
+
+    group_list: List,
+    member_list: List,
+    msgpopup: MsgPopup,
+
+    proxies: ClashProxies,
+    // group shown in `member_list`
+    member_group: String,
+    sort_by_delay: bool,
+
+    delays: HashMap<String, Result<u64, String>>,
+    testing: HashSet<String>,
+    delay_tx: Sender<DelayResult>,
+    delay_rx: Receiver<DelayResult>,
+    // fetched once the tab is first shown
+    loaded: bool,
+    proxy_tx: Sender<Result<ClashProxies, String>>,
+    proxy_rx: Receiver<Result<ClashProxies, String>>,
+
+    clashtui_util: SharedClashTuiUtil,
+    op: Option<PxOp>,
+}
+
+impl ProxyTab {
+    pub fn new(clashtui_util: SharedClashTuiUtil) -> Self {
+        let (delay_tx, delay_rx) = mpsc::channel();
+        let (proxy_tx, proxy_rx) = mpsc::channel();
+        Self {
+            is_visible: false,
+            fouce: Fouce::Group,
+            group_list: List::new(PROXY.to_string()),
+            member_list: List::new(PROXY_MEMBER.to_string()),
+            msgpopup: Default::default(),
+            proxies: Default::default(),
+            member_group: String::new(),
+            sort_by_delay: false,
+
+            delays: HashMap::new(),
+            testing: HashSet::new(),
+            delay_tx,
+            delay_rx,
+            loaded: false,
+            proxy_tx,
+            proxy_rx,
+
+            clashtui_util,
+            op: None,
+        }
+    }
+
+    fn refresh_proxies(&mut self) {
+        self.loaded = true;
+        self.clashtui_util.fetch_proxies(self.proxy_tx.clone());
+    }
+
+    fn recv_proxies(&mut self) {
+        while let Ok(res) = self.proxy_rx.try_recv() {
+            match res {
+                Ok(proxies) => {
+                    self.proxies = proxies;
+                    // the core keeps the latest results in `history`
+                    self.delays.clear();
+                    self.update_group_list();
+                    self.update_member_list();
+                }
+                Err(e) => {
+                    log::warn!("Fetch Proxies:{e}");
+                    // The core might not be running yet, press `r` later
+                    if self.is_visible {
+                        self.popup_txt_msg(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn get_delay(&self, name: &str) -> Option<Result<u64, String>> {
+        self.delays.get(name).cloned().or_else(|| {
+            self.proxies
+                .get(name)
+                .and_then(|p| p.last_delay())
+                .map(|d| if d == 0 { Err("Timeout".to_string()) } else { Ok(d) })
+        })
+    }
+
+    fn render_delay(&self, name: &str) -> (String, Color) {
+        let theme = Theme::get();
+        if self.testing.contains(name) {
+            return ("Testing".to_string(), theme.proxy_delay_unknown_fg);
+        }
+        match self.get_delay(name) {
+            Some(Ok(d)) => (
+                format!("{d}ms"),
+                if d < DELAY_LOW {
+                    theme.proxy_delay_low_fg
+                } else if d < DELAY_MEDIUM {
+                    theme.proxy_delay_medium_fg
+                } else {
+                    theme.proxy_delay_high_fg
+                },
+            ),
+            Some(Err(e)) => (e, theme.proxy_delay_high_fg),
+            None => ("-".to_string(), theme.proxy_delay_unknown_fg),
+        }
+    }
+
+    fn update_group_list(&mut self) {
+        let groups = self.proxies.groups();
+        let extras: Vec<(String, Color)> = groups
+            .iter()
+            .map(|g| {
+                let now = g.now.as_deref().unwrap_or("");
+                let (delay, fg) = self.render_delay(now);
+                if g.is_selectable() {
+                    (format!("{} -> {} {}", g.proxy_type, now, delay), fg)
+                } else {
+                    (format!("{} (read-only)", g.proxy_type), fg)
+                }
+            })
+            .collect();
+        self.group_list
+            .set_items(groups.iter().map(|g| g.name.clone()).collect());
+        self.group_list.set_colored_extras(extras.into_iter());
+    }
+
+    fn update_member_list(&mut self) {
+        let Some(group) = self
+            .group_list
+            .selected()
+            .and_then(|name| self.proxies.get(name))
+        else {
+            self.member_group.clear();
+            self.member_list.set_items(vec![]);
+            return;
+        };
+        let now = group.now.clone().unwrap_or_default();
+        let mut members = group.all.clone();
+        if self.sort_by_delay {
+            // tested first, then failed, then untested
+            members.sort_by_key(|name| match self.get_delay(name) {
+                Some(Ok(d)) => (0, d),
+                Some(Err(_)) => (1, 0),
+                None => (2, 0),
+            });
+        }
+        let extras: Vec<(String, Color)> = members
+            .iter()
+            .map(|name| {
+                let proxy_type = self
+                    .proxies
+                    .get(name)
+                    .map_or("", |p| p.proxy_type.as_str());
+                let (delay, fg) = self.render_delay(name);
+                if name == &now {
+                    (format!("{proxy_type} {delay} (selected)"), fg)
+                } else {
+                    (format!("{proxy_type} {delay}"), fg)
+                }
+            })
+            .collect();
+        // keep the cursor unless another group is shown
+        let cursor = if self.member_group == group.name {
+            self.member_list.selected().cloned()
+        } else {
+            Some(now)
+        };
+        self.member_group.clone_from(&group.name);
+        self.member_list.set_items(members);
+        self.member_list.set_colored_extras(extras.into_iter());
+        if let Some(cursor) = cursor {
+            self.member_list.select(&cursor);
+        }
+    }
+
+    fn handle_select_proxy_ev(&mut self) {
+        if let (Some(group), Some(name)) = (self.group_list.selected(), self.member_list.selected())
+        {
+            if let Err(err) = self.clashtui_util.select_proxy(group, name) {
+                log::error!("Select `{name}` in `{group}` => {err}");
+                self.popup_txt_msg(format!("Failed to Select: {err}"));
+                return;
+            }
+        }
+        self.refresh_proxies();
+    }
+
+    fn handle_delay_test_ev(&mut self) {
+        match self.fouce {
+            Fouce::Group => {
+                let Some(group) = self
+                    .group_list
+                    .selected()
+                    .and_then(|name| self.proxies.get(name))
+                else {
+                    return;
+                };
+                let members: Vec<String> = group
+                    .all
+                    .iter()
+                    .filter(|name| !self.testing.contains(*name))
+                    .cloned()
+                    .collect();
+                self.testing.extend(members.iter().cloned());
+                self.clashtui_util.test_group_delay(
+                    group.name.clone(),
+                    members,
+                    self.delay_tx.clone(),
+                );
+            }
+            Fouce::Member => {
+                let Some(name) = self.member_list.selected() else {
+                    return;
+                };
+                if self.testing.insert(name.clone()) {
+                    self.clashtui_util
+                        .test_proxy_delay(name.clone(), self.delay_tx.clone());
+                }
+            }
+        }
+        self.update_group_list();
+        self.update_member_list();
+    }
+
+    /// Collect finished tests without blocking
+    fn recv_delays(&mut self) {
+        let mut updated = false;
+        while let Ok((name, res)) = self.delay_rx.try_recv() {
+            self.testing.remove(&name);
+            self.delays.insert(name, res);
+            updated = true;
+        }
+        if updated {
+            self.update_group_list();
+            self.update_member_list();
+        }
+    }
+}
+
+use ui::event::{Event, KeyEventKind};
+impl super::TabEvent for ProxyTab {
+    fn popup_event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        self.msgpopup.event(ev)
+    }
+
+    fn event(&mut self, ev: &Event) -> Result<EventState, std::io::Error> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = EventState::NotConsumed;
+        if let Event::Key(key) = ev {
+            if key.kind != KeyEventKind::Press {
+                return Ok(EventState::NotConsumed);
+            }
+
+            event_state = match key.code.into() {
+                Keys::Refresh => {
+                    self.popup_txt_msg("Refreshing...".to_string());
+                    self.op.replace(PxOp::Refresh);
+                    EventState::WorkDone
+                }
+                Keys::ProxyDelayTest => {
+                    self.handle_delay_test_ev();
+                    EventState::WorkDone
+                }
+                Keys::ProxySort => {
+                    self.sort_by_delay = !self.sort_by_delay;
+                    self.update_member_list();
+                    EventState::WorkDone
+                }
+                Keys::Select => {
+                    match self.fouce {
+                        Fouce::Group => self.fouce = Fouce::Member,
+                        Fouce::Member => match self
+                            .group_list
+                            .selected()
+                            .and_then(|name| self.proxies.get(name))
+                        {
+                            Some(group) if !group.is_selectable() => {
+                                let msg = format!(
+                                    "`{}` is a {}, it can't be switched",
+                                    group.name, group.proxy_type
+                                );
+                                self.popup_txt_msg(msg);
+                            }
+                            _ => {
+                                self.popup_txt_msg("Selecting...".to_string());
+                                self.op.replace(PxOp::Select);
+                            }
+                        },
+                    }
+                    EventState::WorkDone
+                }
+                Keys::Esc if self.fouce == Fouce::Member => {
+                    self.fouce = Fouce::Group;
+                    EventState::WorkDone
+                }
+                _ => EventState::NotConsumed,
+            };
+
+            if event_state == EventState::NotConsumed {
+                event_state = match self.fouce {
+                    Fouce::Group => {
+                        let state = self.group_list.event(ev)?;
+                        // keep the members in sync with the group under cursor
+                        self.update_member_list();
+                        state
+                    }
+                    Fouce::Member => self.member_list.event(ev)?,
+                };
+            }
+        }
+
+        Ok(event_state)
+    }
+    fn late_event(&mut self) {
+        // Results might arrive while the tab is hidden
+        self.recv_delays();
+        self.recv_proxies();
+        if self.is_visible && !self.loaded {
+            self.refresh_proxies();
+        }
+        if let Some(op) = self.op.take() {
+            self.hide_msgpopup();
+            match op {
+                PxOp::Refresh => self.refresh_proxies(),
+                PxOp::Select => self.handle_select_proxy_ev(),
+            }
+        }
+    }
+    fn draw(&mut self, f: &mut ratatui::prelude::Frame, area: ratatui::prelude::Rect) {
+        if !self.is_visible() {
+            return;
+        }
+        use ratatui::prelude::{Constraint, Direction, Layout};
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let fouce = self.fouce == Fouce::Group;
+        self.group_list.draw(f, chunks[0], fouce);
+        self.member_list.draw(f, chunks[1], !fouce);
+
+        self.msgpopup.draw(f, area);
+    }
+}
+msgpopup_methods!(ProxyTab, txt);
@@ -0,0 +1,205 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::msgpopup_methods;
+use crate::tui::{
+    symbols::RULE,
+    utils::Keys,
+    widgets::{InputPopup, List, MsgPopup},
+    EventState, Theme, Visibility,
+};
+use crate::utils::SharedClashTuiUtil;
+use api::{ClashRule, ClashRules};
+crate::define_enum!(RlOp, [Refresh]); // RlOp: RuleTabOperation
+
+/// Rules loaded by the running core, which might differ from the merged profile
+#[derive(Visibility)]
+pub struct RuleTab {
+    is_visible: bool,
+
+    rule_list: List,
+    msgpopup: MsgPopup,
+    search_input: InputPopup,
+
+    rules: Vec<ClashRule>,
+    search: String,
+    // number of rules matching `search`
+    shown: usize,
+    // fetched once the tab is first shown
+    loaded: bool,
+    rule_tx: Sender<Result<ClashRules, String>>,
+    rule_rx: Receiver<Result<ClashRules, String>>,
+
+    clashtui_util: SharedClashTuiUtil,
+    op: Option<RlOp>,
+}
+
+impl RuleTab {
+    pub fn new(clashtui_util: SharedClashTuiUtil) -> Self {
+        let (rule_tx, rule_rx) = mpsc::channel();
+        Self {
+            is_visible: false,
+            rule_list: List::new(RULE.to_string()),
+            msgpopup: Default::default(),
+            search_input: InputPopup::new("Search".to_string()),
+
+            rules: vec![],
+            search: String::new(),
+            shown: 0,
+            loaded: false,
+            rule_tx,
+            rule_rx,
+
+            clashtui_util,
+            op: None,
+        }
+    }
+
+    fn refresh_rules(&mut self) {
+        self.loaded = true;
+        self.clashtui_util.fetch_rules(self.rule_tx.clone());
+    }
+
+    fn recv_rules(&mut self) {
+        while let Ok(res) = self.rule_rx.try_recv() {
+            match res {
+                Ok(rules) => {
+                    self.rules = rules.rules;
+                    self.update_rule_list();
+                }
+                Err(e) => {
+                    log::warn!("Fetch Rules:{e}");
+                    // The core might not be running yet, press `r` later
+                    if self.is_visible {
+                        self.popup_txt_msg(e);
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_rule_list(&mut self) {
+        let search = self.search.to_lowercase();
+        // keep the index in the core
+        let (items, extras): (Vec<String>, Vec<String>) = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| {
+                search.is_empty()
+                    || [&r.rule_type, &r.payload, &r.proxy]
+                        .iter()
+                        .any(|s| s.to_lowercase().contains(&search))
+            })
+            .map(|(i, r)| {
+                let mut extra = if r.payload.is_empty() {
+                    format!("-> {}", r.proxy)
+                } else {
+                    format!("{} -> {}", r.payload, r.proxy)
+                };
+                if let Some(size) = r.size() {
+                    extra.push_str(&format!(" ({size})"));
+                }
+                (format!("{:>5} {}", i, r.rule_type), extra)
+            })
+            .unzip();
+        self.shown = items.len();
+        let fg = Theme::get().rule_info_fg;
+        self.rule_list.set_items(items);
+        self.rule_list
+            .set_colored_extras(extras.into_iter().map(|e| (e, fg)));
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!("Rules: {}/{}", self.shown, self.rules.len());
+        if !self.search.is_empty() {
+            summary.push_str(&format!("  Search: {}", self.search));
+        }
+        summary
+    }
+}
+
+use ui::event::{Event, KeyEventKind};
+impl super::TabEvent for RuleTab {
+    fn popup_event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = self.msgpopup.event(ev)?;
+        if event_state.is_notconsumed() {
+            event_state = self.search_input.event(ev)?;
+            // Enter keeps the input, Esc clears it
+            if event_state == EventState::WorkDone && !self.search_input.is_visible() {
+                self.search = self.search_input.get_input_data();
+                self.update_rule_list();
+            }
+        }
+
+        Ok(event_state)
+    }
+
+    fn event(&mut self, ev: &Event) -> Result<EventState, std::io::Error> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+
+        let mut event_state = EventState::NotConsumed;
+        if let Event::Key(key) = ev {
+            if key.kind != KeyEventKind::Press {
+                return Ok(EventState::NotConsumed);
+            }
+
+            event_state = match key.code.into() {
+                Keys::Search => {
+                    self.search_input.set_pre_data(self.search.clone());
+                    self.search_input.show();
+                    EventState::WorkDone
+                }
+                Keys::Refresh => {
+                    self.popup_txt_msg("Refreshing...".to_string());
+                    self.op.replace(RlOp::Refresh);
+                    EventState::WorkDone
+                }
+                _ => EventState::NotConsumed,
+            };
+
+            if event_state == EventState::NotConsumed {
+                event_state = self.rule_list.event(ev)?;
+            }
+        }
+
+        Ok(event_state)
+    }
+    fn late_event(&mut self) {
+        self.recv_rules();
+        if self.is_visible && !self.loaded {
+            self.refresh_rules();
+        }
+        if let Some(op) = self.op.take() {
+            self.hide_msgpopup();
+            match op {
+                RlOp::Refresh => self.refresh_rules(),
+            }
+        }
+    }
+    fn draw(&mut self, f: &mut ratatui::prelude::Frame, area: ratatui::prelude::Rect) {
+        if !self.is_visible() {
+            return;
+        }
+        use ratatui::prelude::{Constraint, Layout};
+        use ratatui::widgets::{Block, Borders, Paragraph};
+
+        let chunks = Layout::default()
+            .constraints([Constraint::Length(3), Constraint::Min(0)])
+            .split(area);
+        f.render_widget(
+            Paragraph::new(self.summary()).block(Block::default().borders(Borders::ALL)),
+            chunks[0],
+        );
+        self.rule_list.draw(f, chunks[1], true);
+
+        self.search_input.draw(f, chunks[0], true);
+        self.msgpopup.draw(f, area);
+    }
+}
+msgpopup_methods!(RuleTab, txt);
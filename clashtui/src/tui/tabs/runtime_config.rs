@@ -0,0 +1,270 @@
+use ratatui::{prelude as Ra, widgets as Raw};
+use serde_json::{json, Map, Value};
+use ui::event::{Event, KeyEventKind};
+
+use crate::tui::{
+    tools,
+    utils::Keys,
+    widgets::{InputPopup, List},
+    EventState, Visibility,
+};
+use api::{ClashConfig, LogLevel};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    AllowLan,
+    BindAddress,
+    MixedPort,
+    Port,
+    SocksPort,
+    RedirPort,
+    TproxyPort,
+    LogLevel,
+    Ipv6,
+    Sniffing,
+    TcpConcurrent,
+    InterfaceName,
+}
+const FIELDS: [Field; 12] = [
+    Field::AllowLan,
+    Field::BindAddress,
+    Field::MixedPort,
+    Field::Port,
+    Field::SocksPort,
+    Field::RedirPort,
+    Field::TproxyPort,
+    Field::LogLevel,
+    Field::Ipv6,
+    Field::Sniffing,
+    Field::TcpConcurrent,
+    Field::InterfaceName,
+];
+const LOG_LEVELS: [LogLevel; 5] = [
+    LogLevel::Silent,
+    LogLevel::Error,
+    LogLevel::Warning,
+    LogLevel::Info,
+    LogLevel::Debug,
+];
+
+impl Field {
+    /// Key of `PATCH /configs`
+    fn key(self) -> &'static str {
+        match self {
+            Field::AllowLan => "allow-lan",
+            Field::BindAddress => "bind-address",
+            Field::MixedPort => "mixed-port",
+            Field::Port => "port",
+            Field::SocksPort => "socks-port",
+            Field::RedirPort => "redir-port",
+            Field::TproxyPort => "tproxy-port",
+            Field::LogLevel => "log-level",
+            Field::Ipv6 => "ipv6",
+            Field::Sniffing => "sniffing",
+            Field::TcpConcurrent => "tcp-concurrent",
+            Field::InterfaceName => "interface-name",
+        }
+    }
+    fn value(self, cfg: &ClashConfig) -> Value {
+        match self {
+            Field::AllowLan => json!(cfg.allow_lan),
+            Field::BindAddress => json!(cfg.bind_address),
+            Field::MixedPort => json!(cfg.mixed_port),
+            Field::Port => json!(cfg.port),
+            Field::SocksPort => json!(cfg.socks_port),
+            Field::RedirPort => json!(cfg.redir_port),
+            Field::TproxyPort => json!(cfg.tproxy_port),
+            Field::LogLevel => json!(cfg.log_level.to_string()),
+            Field::Ipv6 => json!(cfg.ipv6),
+            Field::Sniffing => json!(cfg.sniffing),
+            Field::TcpConcurrent => json!(cfg.tcp_concurrent),
+            Field::InterfaceName => json!(cfg.interface_name),
+        }
+    }
+    fn is_port(self) -> bool {
+        matches!(
+            self,
+            Field::MixedPort
+                | Field::Port
+                | Field::SocksPort
+                | Field::RedirPort
+                | Field::TproxyPort
+        )
+    }
+}
+
+pub enum ConfigAction {
+    /// Apply to the core at once
+    Patch(Value),
+    /// Save what was applied into the config files
+    Persist,
+    Invalid(String),
+}
+
+/// Fields of the running config that the core can change without reloading
+#[derive(Visibility)]
+pub struct RuntimeConfigPopup {
+    is_visible: bool,
+    field_list: List,
+    input: InputPopup,
+    editing: Option<Field>,
+
+    // of FIELDS
+    values: Vec<Value>,
+    // applied since the last save, in the form of `PATCH /configs`
+    changed: Map<String, Value>,
+    action: Option<ConfigAction>,
+}
+
+impl RuntimeConfigPopup {
+    pub fn new() -> Self {
+        let mut field_list = List::new("Runtime Config".to_string());
+        field_list.set_items(FIELDS.iter().map(|f| f.key().to_string()).collect());
+        Self {
+            is_visible: false,
+            field_list,
+            input: InputPopup::new("Value".to_string()),
+            editing: None,
+
+            values: vec![],
+            changed: Map::new(),
+            action: None,
+        }
+    }
+
+    pub fn event(&mut self, ev: &Event) -> Result<EventState, ui::Infailable> {
+        if !self.is_visible {
+            return Ok(EventState::NotConsumed);
+        }
+        let Event::Key(key) = ev else {
+            return Ok(EventState::NotConsumed);
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(EventState::WorkDone);
+        }
+
+        if let Some(field) = self.editing {
+            if Keys::Esc == *key {
+                self.input.hide();
+                self.input.handle_esc_ev();
+                self.editing = None;
+            } else {
+                self.input.event(ev)?;
+                if !self.input.is_visible() {
+                    self.editing = None;
+                    self.action = Some(parse_input(field, self.input.get_input_data()));
+                }
+            }
+            return Ok(EventState::WorkDone);
+        }
+
+        match key.code.into() {
+            Keys::Select => self.handle_select_ev(),
+            Keys::Save => self.action = Some(ConfigAction::Persist),
+            Keys::Esc => self.hide(),
+            _ => {
+                self.field_list.event(ev)?;
+            }
+        }
+        Ok(EventState::WorkDone)
+    }
+
+    fn handle_select_ev(&mut self) {
+        let Some((field, current)) = self
+            .field_list
+            .selected_index()
+            .and_then(|i| Some((*FIELDS.get(i)?, self.values.get(i)?.clone())))
+        else {
+            return;
+        };
+        if let Some(b) = current.as_bool() {
+            self.action = Some(ConfigAction::Patch(json!({ field.key(): !b })));
+        } else if field == Field::LogLevel {
+            let i = LOG_LEVELS
+                .iter()
+                .position(|l| current.as_str() == Some(l.to_string().as_str()))
+                .unwrap_or_default();
+            let next = LOG_LEVELS[(i + 1) % LOG_LEVELS.len()];
+            self.action = Some(ConfigAction::Patch(
+                json!({ field.key(): next.to_string() }),
+            ));
+        } else {
+            self.input.set_pre_data(display(&current));
+            self.input.show();
+            self.editing = Some(field);
+        }
+    }
+
+    pub fn take_action(&mut self) -> Option<ConfigAction> {
+        self.action.take()
+    }
+
+    /// Show the values of `config`
+    pub fn set_config(&mut self, config: ClashConfig) {
+        self.values = FIELDS.iter().map(|f| f.value(&config)).collect();
+        let extras: Vec<String> = FIELDS
+            .iter()
+            .zip(self.values.iter())
+            .map(|(f, v)| {
+                let value = display(v);
+                if self.changed.contains_key(f.key()) {
+                    format!("{value} (unsaved)")
+                } else {
+                    value
+                }
+            })
+            .collect();
+        self.field_list.set_extras(extras.into_iter());
+    }
+
+    /// Remember `patch` which the core accepted
+    pub fn applied(&mut self, patch: Value) {
+        if let Value::Object(map) = patch {
+            self.changed.extend(map);
+        }
+    }
+    /// Fields applied since the last save
+    pub fn changed(&self) -> Value {
+        Value::Object(self.changed.clone())
+    }
+    pub fn saved(&mut self) {
+        self.changed.clear();
+    }
+
+    pub fn draw(&mut self, f: &mut Ra::Frame) {
+        if !self.is_visible {
+            return;
+        }
+        let area = tools::centered_percent_rect(60, 60, f.size());
+        f.render_widget(Raw::Clear, area);
+        self.field_list.draw(f, area, self.editing.is_none());
+
+        let input_area = Ra::Layout::default()
+            .constraints([Ra::Constraint::Length(3), Ra::Constraint::Min(0)])
+            .horizontal_margin(2)
+            .vertical_margin(1)
+            .split(area)[0];
+        self.input.draw(f, input_area, true);
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn parse_input(field: Field, input: String) -> ConfigAction {
+    let input = input.trim();
+    if field.is_port() {
+        match input.parse::<u16>() {
+            Ok(port) => ConfigAction::Patch(json!({ field.key(): port })),
+            Err(_) => ConfigAction::Invalid(format!("Invalid port `{input}`")),
+        }
+    } else if field == Field::BindAddress && input.is_empty() {
+        ConfigAction::Invalid("Empty bind-address, use `*` for all".to_string())
+    } else {
+        ConfigAction::Patch(json!({ field.key(): input }))
+    }
+}
@@ -1,17 +1,22 @@
 use crate::tui::{EventState, Visibility};
 use ui::event::Event;
 use ratatui::prelude as Ra;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use crate::utils::{is_newer, LatestRelease};
 
 use super::list_popup::PopUp;
 
 pub struct InfoPopUp {
     inner: PopUp,
-    items: HashMap<Infos, String>,
+    // ordered by `Infos`
+    items: BTreeMap<Infos, String>,
+    changelog: Vec<String>,
 }
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Infos {
     TuiVer,
+    TuiLatest,
     MihomoVer,
 }
 impl core::fmt::Display for Infos {
@@ -21,6 +26,7 @@ impl core::fmt::Display for Infos {
             "{}",
             match self {
                 Infos::TuiVer => "ClashTui:".to_string(),
+                Infos::TuiLatest => "Latest:".to_string(),
                 Infos::MihomoVer => "Mihomo:".to_string(),
             }
         )
@@ -34,16 +40,55 @@ impl InfoPopUp {
         } else {
             return;
         }
-        self.inner
-            .set_items(self.items.iter().map(|(k, v)| format!("{k}:{v}")))
+        self.update_items()
+    }
+    /// Show `release` of clashtui, with the changelog if it's newer
+    pub fn set_release(&mut self, release: &LatestRelease) {
+        if release.tag_name.is_empty() {
+            return;
+        }
+        let newer = is_newer(&release.tag_name, crate::VERSION);
+        let mut latest = format!(
+            "{} ({})",
+            release.tag_name,
+            match newer {
+                Some(true) => "update available",
+                Some(false) => "up to date",
+                None => "unknown",
+            }
+        );
+        if !release.staged.is_empty() {
+            latest.push_str(&format!(", downloaded to {}", release.staged));
+        }
+        self.items.insert(Infos::TuiLatest, latest);
+        self.changelog.clear();
+        if newer == Some(true) {
+            self.changelog.push(String::new());
+            self.changelog.push(format!("Changelog {}", release.url));
+            self.changelog
+                .extend(release.changelog.lines().map(|l| l.trim_end().to_string()));
+        }
+        self.update_items()
     }
     pub fn with_items(mihomover: &str) -> Self {
-        let mut items = HashMap::new();
+        let mut items = BTreeMap::new();
         items.insert(Infos::TuiVer, crate::VERSION.to_string());
         items.insert(Infos::MihomoVer, mihomover.to_owned());
-        let mut inner = PopUp::new("Info".to_string());
-        inner.set_items(items.iter().map(|(k, v)| format!("{k}:{v}")));
-        Self { items, inner }
+        let mut info = Self {
+            items,
+            changelog: vec![],
+            inner: PopUp::new("Info".to_string()),
+        };
+        info.update_items();
+        info
+    }
+    fn update_items(&mut self) {
+        self.inner.set_items(
+            self.items
+                .iter()
+                .map(|(k, v)| format!("{k}:{v}"))
+                .chain(self.changelog.iter().cloned()),
+        )
     }
 }
 
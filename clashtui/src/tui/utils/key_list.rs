@@ -10,6 +10,16 @@ pub enum Keys {
     TemplateSwitch,
     Edit,
     Preview,
+    Refresh,
+    ProxyDelayTest,
+    ProxySort,
+    ProviderHealthCheckAll,
+    Search,
+    ConnClose,
+    ConnCloseAll,
+    LogLevel,
+    LogPause,
+    Save,
 
     Down,
     Up,
@@ -41,6 +51,7 @@ impl From<KeyCode> for Keys {
             KeyCode::Enter => Keys::Select,
             KeyCode::Esc => Keys::Esc,
             KeyCode::Tab => Keys::Tab,
+            KeyCode::Char('w') => Keys::Save,      // the logs, or the runtime config
 
             // ## Profile Tab shortcuts
             KeyCode::Char('p') => Keys::ProfileSwitch,      // Not Global shortcuts
@@ -57,6 +68,23 @@ impl From<KeyCode> for Keys {
             KeyCode::Char('d') => Keys::ProfileDelete,
             KeyCode::Char('s') => Keys::ProfileTestConfig,
 
+            // ## Proxy Tab shortcuts
+            KeyCode::Char('r') => Keys::Refresh,
+            KeyCode::Char('c') => Keys::ProxyDelayTest,
+            KeyCode::Char('o') => Keys::ProxySort,
+
+            // ## Provider Tab shortcuts, `u`, `a` and `c` are shared with the above
+            KeyCode::Char('b') => Keys::ProviderHealthCheckAll,     // a batch of `c`
+
+            // ## Connection Tab shortcuts
+            KeyCode::Char('/') => Keys::Search,
+            KeyCode::Char('x') => Keys::ConnClose,
+            KeyCode::Char('z') => Keys::ConnCloseAll,      // next to `x`, asks for confirmation
+
+            // ## Log Tab shortcuts
+            KeyCode::Char('f') => Keys::LogLevel,
+            KeyCode::Char(' ') => Keys::LogPause,
+
             // ## Global Shortcuts (As much as possible use uppercase. And Others as much as possible use lowcase to avoid conflicts with global shortcuts.)
             KeyCode::Char('q') => Keys::AppQuit,   // Exiting is a common operation, and most software also exits with "q", so let's use "q".
             KeyCode::Char('R') => Keys::SoftRestart,
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::result::Result;
 use std::error::Error;
@@ -7,6 +8,31 @@ use std::error::Error;
 #[serde(default)]
 pub struct ClashTuiData {
     pub current_profile: String,
+    // id of the meta-rules-dat release in `clash_cfg_dir`
+    pub geo_release_id: String,
+    pub latest_release: LatestRelease,
+    // by profile name
+    pub profiles: BTreeMap<String, ProfileData>,
+}
+
+/// What is learned while updating a profile
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileData {
+    pub subscription: Option<api::SubscriptionInfo>,
+    pub update_interval: Option<u64>,   // hours, from `profile-update-interval`
+    pub last_update: i64,               // unix time of the last successful update
+}
+
+/// The latest release of clashtui, checked at most once a day
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatestRelease {
+    pub tag_name: String,
+    pub changelog: String,
+    pub url: String,
+    pub checked_at: i64,    // unix time
+    pub staged: String,     // path of the downloaded binary, empty if none
 }
 
 impl ClashTuiData {
@@ -8,9 +8,24 @@ pub struct ClashTuiConfig {
     pub clash_core_path: String,
     pub clash_srv_name: String,
     pub clash_srv_is_user: bool,        // true: systemctl --user ...
+    pub core_mirror: String,            // prefixed to github downloads of InstallCore, e.g. https://ghproxy.net/
 
     pub edit_cmd: String,
     pub open_dir_cmd: String,
+
+    pub delay_test_url: String,
+    pub delay_test_timeout: u64,        // ms
+    pub profile_update_interval: u64,   // hours, for profiles without `profile-update-interval`, 0 for 24
+    pub update_concurrency: usize,      // providers downloaded at once, 0 for 4
+
+    // For `external-controller-tls`, the public roots are trusted if both are empty
+    pub controller_tls_ca: String,          // PEM file of CA certificates, relative to clash_cfg_dir
+    pub controller_tls_fingerprint: String, // SHA-256 of the controller certificate, takes precedence
+
+    pub network: api::NetConfig,        // timeouts and retries, missing keys are default
+
+    pub release_url: String,            // github api of clashtui releases, empty for the default
+    pub release_stage_dir: String,      // download new releases of clashtui here, empty to only check
 }
 impl ClashTuiConfig {
     pub fn from_file(config_path: &str) -> Result<Self> {
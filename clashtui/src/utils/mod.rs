@@ -13,6 +13,9 @@ pub type SharedClashTuiState = std::rc::Rc<core::cell::RefCell<State>>;
 pub use config::{init_config, CfgError};
 pub use flags::{BitFlags as Flags, Flag};
 pub use state::State;
-pub use tui::{ClashTuiUtil, ProfileType};
+pub use tui::{
+    is_newer, str_subscription, ClashTuiUtil, CoreLogs, CoreStat, DelayResult, GeoUpdate,
+    ProfileType, ProfileUpdate, ProviderOp, ProviderResult,
+};
 pub use utils::*;
-pub use clashtui_data::ClashTuiData;
+pub use clashtui_data::{ClashTuiData, LatestRelease};
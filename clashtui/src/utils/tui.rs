@@ -1,5 +1,4 @@
 use core::cell::RefCell;
-use core::str::FromStr as _;
 use std::{
     io::Error,
     path::{Path, PathBuf},
@@ -8,14 +7,35 @@ use api::ProfileSectionType;
 
 mod impl_app;
 mod impl_clashsrv;
+mod impl_config;
+mod impl_connection;
+mod impl_corelog;
+mod impl_geo;
 mod impl_profile;
+mod impl_provider;
+mod impl_proxy;
+mod impl_release;
+mod impl_rule;
+mod impl_traffic;
+mod impl_update;
+#[cfg(test)]
+mod test_dir;
+
+pub use impl_profile::str_subscription;
+pub use impl_provider::{ProviderOp, ProviderResult};
+pub use impl_proxy::DelayResult;
+pub use impl_release::is_newer;
+pub use impl_corelog::CoreLogs;
+pub use impl_geo::GeoUpdate;
+pub use impl_traffic::CoreStat;
+pub use impl_update::ProfileUpdate;
 
 use super::{
     config::{CfgError, ClashTuiConfig, ErrKind},
     parse_yaml,
     ClashTuiData,
 };
-use api::{ClashConfig, ClashUtil, Resp};
+use api::{ClashConfig, ClashUtil};
 
 // format: {section_key: [(name, url, path)]}
 pub type NetProviderMap = std::collections::HashMap<ProfileSectionType, Vec<(String, String, String)>>;
@@ -28,6 +48,10 @@ pub enum ProfileType {
 
 const BASIC_FILE: &str = "basic_clash_config.yaml";
 const DATA_FILE: &str = "data.yaml";
+// The default of mihomo, tried when the basic config has none
+const DEFAULT_CONTROLLER: &str = "http://127.0.0.1:9090";
+// The default `port` of mihomo, tried when the basic config has no ports
+const DEFAULT_PROXY: &str = "http://127.0.0.1:7890";
 
 pub struct ClashTuiUtil {
     pub clashtui_dir: PathBuf,
@@ -69,7 +93,7 @@ impl ClashTuiUtil {
     }
     pub fn clash_version(&self) -> String {
         match self.clash_api.version() {
-            Ok(v) => v,
+            Ok(v) => v.to_string(),
             Err(e) => {
                 log::warn!("{}", e);
                 "Unknown".to_string()
@@ -77,20 +101,16 @@ impl ClashTuiUtil {
         }
     }
     fn fetch_remote(&self) -> Result<ClashConfig, Error> {
-        self.clash_api.config_get().and_then(|cur_remote| {
-            ClashConfig::from_str(cur_remote.as_str())
-                .map_err(|_| Error::new(std::io::ErrorKind::InvalidData, "Failed to prase str"))
-        })
+        self.clash_api.config_get().map_err(Error::from)
     }
     pub fn restart_clash(&self) -> Result<String, Error> {
-        self.clash_api.restart(None)
-    }
-    fn dl_remote_profile(&self, url: &str) -> Result<Resp, Error> {
-        let with_proxy = self.clash_api.version().is_ok();
-        self.clash_api.mock_clash_core(url, with_proxy)
+        self.clash_api
+            .restart(None)
+            .map(|_| "Clash restarted".to_string())
+            .map_err(Error::from)
     }
     fn config_reload(&self, body: String) -> Result<(), Error> {
-        self.clash_api.config_reload(body)
+        self.clash_api.config_reload(body).map_err(Error::from)
     }
 
     pub fn save_to_data_file(&self) {
@@ -116,24 +136,13 @@ fn load_app_config(
                 serde_yaml::Value::Null
             }
         };
-    let controller_api = basic_clash_config_value
-        .get("external-controller")
-        .and_then(|v| {
-                let controller_address = v.as_str().expect("external-controller not str?");
-                if controller_address.starts_with("0.0.0.0:") {
-                    // replace 0.0.0.0 with 127.0.0.1
-                    let port_index = controller_address.find(':').unwrap_or(0) + 1;
-                    let port = &controller_address[port_index..];
-                    let machine_ip = "127.0.0.1";
-                    Some(format!("http://{machine_ip}:{port}"))
-                } else {
-                    Some(format!("http://{}", controller_address))
-                }
-        })
-        .unwrap_or_else(|| panic!("No external-controller in {BASIC_FILE}"));
-    log::info!("controller_api: {}", controller_api);
-
-    let proxy_addr = get_proxy_addr(&basic_clash_config_value);
+    let proxy_addr = get_proxy_addr(&basic_clash_config_value).unwrap_or_else(|| {
+        err_collect.push(CfgError::new(
+            ErrKind::LoadProfileConfig,
+            format!("No ports in {BASIC_FILE}"),
+        ));
+        DEFAULT_PROXY.to_string()
+    });
     log::info!("proxy_addr: {}", proxy_addr);
 
     let secret = basic_clash_config_value
@@ -175,23 +184,170 @@ fn load_app_config(
     } else {
         ClashTuiConfig::default()
     };
-    (
-        configs,
-        ClashUtil::new(controller_api, secret, proxy_addr, clash_ua),
-        err_collect,
-    )
+    let get_str = |key: &str| {
+        basic_clash_config_value
+            .get(key)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+    };
+    // a broken tls setup falls back to the others
+    let tls_api = get_str("external-controller-tls").and_then(|addr| {
+        let addr = local_controller(addr);
+        log::info!("controller_tls: {}", addr);
+        ClashUtil::new_tls(
+            &addr,
+            &controller_tls_trust(&configs),
+            secret.clone(),
+            proxy_addr.clone(),
+            clash_ua.clone(),
+            configs.network,
+        )
+        .map_err(|e| {
+            err_collect.push(CfgError::new(
+                ErrKind::LoadProfileConfig,
+                format!("external-controller-tls: {e}"),
+            ))
+        })
+        .ok()
+    });
+    let clash_api = if let Some(socket) = get_str("external-controller-unix") {
+        // mihomo takes a relative path from its home dir
+        let socket = Path::new(&configs.clash_cfg_dir).join(socket);
+        log::info!("controller_unix: {}", socket.display());
+        ClashUtil::new_unix(socket, secret, proxy_addr, clash_ua, configs.network)
+    } else if let Some(api) = tls_api {
+        api
+    } else {
+        let controller_api = get_str("external-controller")
+            .map(|addr| format!("http://{}", local_controller(addr)))
+            .unwrap_or_else(|| {
+                err_collect.push(CfgError::new(
+                    ErrKind::LoadProfileConfig,
+                    format!("No usable external-controller in {BASIC_FILE}"),
+                ));
+                DEFAULT_CONTROLLER.to_string()
+            });
+        log::info!("controller_api: {}", controller_api);
+        ClashUtil::new(controller_api, secret, proxy_addr, clash_ua, configs.network)
+    };
+    (configs, clash_api, err_collect)
 }
 
-fn get_proxy_addr(yaml_data: &serde_yaml::Value) -> String {
+/// Replace `0.0.0.0` with `127.0.0.1`
+fn local_controller(addr: &str) -> String {
+    match addr.strip_prefix("0.0.0.0:") {
+        Some(port) => format!("127.0.0.1:{port}"),
+        None => addr.to_string(),
+    }
+}
+
+fn controller_tls_trust(configs: &ClashTuiConfig) -> api::TlsTrust {
+    if !configs.controller_tls_fingerprint.is_empty() {
+        api::TlsTrust::Fingerprint(configs.controller_tls_fingerprint.clone())
+    } else if !configs.controller_tls_ca.is_empty() {
+        api::TlsTrust::CaBundle(Path::new(&configs.clash_cfg_dir).join(&configs.controller_tls_ca))
+    } else {
+        api::TlsTrust::System
+    }
+}
+
+fn get_proxy_addr(yaml_data: &serde_yaml::Value) -> Option<String> {
     let host = "127.0.0.1";
     if let Some(port) = yaml_data.get("mixed-port").and_then(|v| v.as_u64()) {
-        return format!("http://{}:{}", host, port);
+        return Some(format!("http://{}:{}", host, port));
     }
     if let Some(port) = yaml_data.get("port").and_then(|v| v.as_u64()) {
-        return format!("http://{}:{}", host, port);
+        return Some(format!("http://{}:{}", host, port));
     }
     if let Some(port) = yaml_data.get("socks-port").and_then(|v| v.as_u64()) {
-        return format!("socks5://{}:{}", host, port);
+        return Some(format!("socks5://{}:{}", host, port));
+    }
+    None
+}
+
+/// Write `body` to a temp file next to `path` and rename it over `path`
+///
+/// `path` is either replaced as a whole or left untouched.
+fn replace_file(path: &Path, body: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Invalid file path"))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    let replace = || -> std::io::Result<()> {
+        let mut tmp_file = std::fs::File::create(&tmp_path)?;
+        tmp_file.write_all(body)?;
+        tmp_file.sync_all()?;
+        // keep the perms fixed by `check_perms_of_ccd_files`, a new file gets
+        // the primary group of the user instead
+        if let Ok(meta) = std::fs::metadata(path) {
+            use std::os::unix::fs::MetadataExt;
+            if tmp_file.metadata()?.gid() != meta.gid() {
+                nix::unistd::chown(&tmp_path, None, Some(nix::unistd::Gid::from_raw(meta.gid())))?;
+            }
+            std::fs::set_permissions(&tmp_path, meta.permissions())?;
+        }
+        std::fs::rename(&tmp_path, path)
+    };
+    replace().inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_dir::TestDir;
+    use super::ClashTuiUtil;
+    use api::mock::{MockController, MOCK_CA_PEM, MOCK_CERT_SHA256, MOCK_VERSION};
+
+    #[test]
+    fn clash_version_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("version", &mock);
+        assert_eq!(dir.util().clash_version(), MOCK_VERSION);
+    }
+    #[test]
+    fn unix_controller_test() {
+        let dir = TestDir::with_controller("unix", "external-controller-unix: mihomo.sock");
+        // relative to clash_cfg_dir
+        let _mock = MockController::start_unix(dir.0.join("mihomo.sock"), "test");
+        assert_eq!(dir.util().clash_version(), MOCK_VERSION);
+    }
+    #[test]
+    fn tls_controller_test() {
+        let mock = MockController::start_tls("test");
+        let dir = TestDir::with_controller(
+            "tls",
+            &format!("external-controller-tls: {}", mock.addr()),
+        );
+        std::fs::write(dir.0.join("ca.pem"), MOCK_CA_PEM).unwrap();
+        let cfg = std::fs::read_to_string(dir.0.join("config.yaml")).unwrap();
+        std::fs::write(
+            dir.0.join("config.yaml"),
+            format!("{cfg}controller_tls_ca: ca.pem\n"),
+        )
+        .unwrap();
+        assert_eq!(dir.util().clash_version(), MOCK_VERSION);
+
+        std::fs::write(
+            dir.0.join("config.yaml"),
+            format!("{cfg}controller_tls_fingerprint: {MOCK_CERT_SHA256}\n"),
+        )
+        .unwrap();
+        assert_eq!(dir.util().clash_version(), MOCK_VERSION);
+    }
+    #[test]
+    fn no_controller_test() {
+        let dir = TestDir::with_controller("no_controller", "");
+        let (_, errs) = ClashTuiUtil::new(&dir.0, true);
+        assert!(!errs.is_empty());
+        // no ports either
+        let dir = TestDir::with_basic("no_ports", "");
+        let (_, errs) = ClashTuiUtil::new(&dir.0, true);
+        assert!(!errs.is_empty());
+        // nor the basic config
+        std::fs::remove_file(dir.0.join(super::BASIC_FILE)).unwrap();
+        let (_, errs) = ClashTuiUtil::new(&dir.0, true);
+        assert!(!errs.is_empty());
     }
-    panic!("No prots in {BASIC_FILE}")
 }
@@ -28,9 +28,9 @@ impl ClashTuiUtil {
         use crate::utils::ipc::spawn;
         if !cmd.is_empty() {
             let open_cmd = cmd.replace("%s", path.to_str().unwrap_or(""));
-            return spawn("sh", vec!["-c", open_cmd.as_str()]);
+            spawn("sh", vec!["-c", open_cmd.as_str()])
         } else {
-            return spawn("xdg-open", vec![path.to_str().unwrap_or("")]);
+            spawn("xdg-open", vec![path.to_str().unwrap_or("")])
         }
     }
 
@@ -78,3 +78,21 @@ impl ClashTuiUtil {
         (pf, mode, tun)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn update_state_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("state", &mock);
+        let util = dir.util();
+        let state = util.update_state(Some("profile1".to_string()), Some("Global".to_string()));
+        assert_eq!(state.profile, "profile1");
+        assert!(matches!(state.mode, Some(api::Mode::Global)));
+        assert!(state.tun.is_none());
+        assert_eq!(mock.config()["mode"], "global");
+    }
+}
@@ -5,7 +5,13 @@ use crate::utils::{
     utils as toolkit,
 };
 
+use api::DnsQuery;
 use std::io::Error;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+// The core restarts itself after upgrading, wait for it this long
+const UPGRADE_WAIT: Duration = Duration::from_secs(15);
 
 impl ClashTuiUtil {
     pub fn clash_srv_ctl(&self, op: ClashSrvOp) -> Result<String, Error> {
@@ -40,6 +46,14 @@ impl ClashTuiUtil {
 
                 Ok(format!("# ## stop\n{output1}# ## status\n{output2}"))
             }
+            ClashSrvOp::RollbackCore => {
+                api::rollback_mihomo(&self.tui_cfg.clash_core_path)?;
+                Ok(format!(
+                    "Rolled back {}\nRestart the core to use it",
+                    self.tui_cfg.clash_core_path
+                ))
+            }
+            ClashSrvOp::SwitchTun => self.set_tun(!self.runtime_config()?.tun.enable),
             ClashSrvOp::SetPermission => {
                 let pgm = "setcap";
                 let args = vec![
@@ -55,10 +69,100 @@ impl ClashTuiUtil {
                     ipc::exec_with_sbin("pkexec", cmd)
                 }
             }
+            ClashSrvOp::GeoDataStatus => Ok(self.geo_data_status().join("\n")),
+            ClashSrvOp::FlushFakeIp => self
+                .clash_api
+                .flush_fakeip()
+                .map(|_| "Fake-IP cache flushed".to_string())
+                .map_err(Error::from),
+            ClashSrvOp::FlushDnsCache => match self.clash_api.flush_dns_cache() {
+                Ok(_) => Ok("DNS cache flushed".to_string()),
+                Err(e) if e.is_not_found() => Err(Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("The core doesn't support flushing dns cache, {e}"),
+                )),
+                Err(e) => Err(e.into()),
+            },
             _ => Err(Error::new(
                 std::io::ErrorKind::NotFound,
                 "No Support Action",
             )),
         }
     }
+    /// Upgrade the core, or the dashboard if `ui`, in background
+    ///
+    /// Progress is sent line by line, the channel is closed once done
+    pub fn upgrade_core(&self, ui: bool) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            let version = || {
+                api.version()
+                    .map_or_else(|e| format!("Unknown ({e})"), |v| v.to_string())
+            };
+            let _ = tx.send(format!("Version before: {}", version()));
+            let (target, res) = if ui {
+                let _ = tx.send("Upgrading the dashboard...".to_string());
+                ("Dashboard", api.upgrade_ui())
+            } else {
+                let _ = tx.send("Upgrading the core...".to_string());
+                ("Core", api.upgrade())
+            };
+            if let Err(e) = res {
+                log::error!("Upgrade {target} => {e}");
+                let _ = tx.send(format!("Failed to Upgrade: {e}"));
+                return;
+            }
+            let _ = tx.send(format!("{target} upgraded"));
+            if !ui {
+                let _ = tx.send("Waiting for the core to restart...".to_string());
+                let start = std::time::Instant::now();
+                // the old process might still answer right after the request
+                std::thread::sleep(Duration::from_secs(1));
+                while api.version().is_err() && start.elapsed() < UPGRADE_WAIT {
+                    std::thread::sleep(Duration::from_millis(500));
+                }
+            }
+            let _ = tx.send(format!("Version after: {}", version()));
+        });
+        rx
+    }
+    /// Install the latest mihomo from github into `clash_core_path` in background
+    ///
+    /// Progress is sent line by line, the channel is closed once done
+    pub fn install_core(&self) -> Receiver<String> {
+        let (tx, rx) = mpsc::channel();
+        let api = self.clash_api.clone();
+        let core_path = self.tui_cfg.clash_core_path.clone();
+        let mirror = self.tui_cfg.core_mirror.clone();
+        std::thread::spawn(move || {
+            let mut opts = api::MihomoInstall::new(&core_path);
+            opts.mirror = mirror;
+            opts.with_proxy = api.version().is_ok();
+            let _ = tx.send("Downloading the core...".to_string());
+            let installed = match api.install_mihomo(&opts) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Install Core => {e}");
+                    let _ = tx.send(format!("Failed to Install: {e}"));
+                    return;
+                }
+            };
+            let _ = tx.send(format!(
+                "Installed {} ({}) to {core_path}",
+                installed.tag_name, installed.asset
+            ));
+            if let Some(backup) = installed.backup {
+                let _ = tx.send(format!("The previous one is kept at {}", backup.display()));
+            }
+            let _ = tx.send("Run SetPermission for TUN, then restart the core".to_string());
+        });
+        rx
+    }
+    /// Ask the resolver of the core, fake-ip answers are kept as is
+    pub fn dns_query(&self, name: &str, record_type: &str) -> Result<DnsQuery, Error> {
+        self.clash_api
+            .dns_query(name, record_type)
+            .map_err(Error::from)
+    }
 }
@@ -0,0 +1,155 @@
+use super::ClashTuiUtil;
+use crate::utils::utils as Utils;
+use api::{ClashConfig, TunStack};
+use serde_json::{json, Value};
+use std::{io::Error, path::Path, time::{Duration, Instant}};
+
+// The core brings the device up in background, wait for it this long
+const TUN_WAIT: Duration = Duration::from_secs(2);
+
+impl ClashTuiUtil {
+    /// The running config of the core
+    pub fn runtime_config(&self) -> Result<ClashConfig, Error> {
+        self.fetch_remote()
+    }
+
+    /// Change the running core at once, e.g. `{"allow-lan": true}`
+    pub fn patch_runtime_config(&self, patch: &Value) -> Result<(), Error> {
+        self.clash_api
+            .config_patch(patch.to_string())
+            .map_err(Error::from)
+    }
+
+    /// Write a patch of [patch_runtime_config][Self::patch_runtime_config] into the basic config,
+    /// and the config in use, so that it survives restarts and switching profiles
+    pub fn persist_runtime_config(&self, patch: &Value) -> Result<(), Error> {
+        patch_yaml_file(&self.clashtui_dir.join(super::BASIC_FILE), patch)?;
+        let final_cfg = Path::new(&self.tui_cfg.clash_cfg_path);
+        if final_cfg.is_file() {
+            patch_yaml_file(final_cfg, patch)?;
+        }
+        Ok(())
+    }
+
+    /// Turn TUN on or off
+    ///
+    /// The core accepts the patch even if it fails to create the device,
+    /// so read back the config to tell
+    pub fn set_tun(&self, enable: bool) -> Result<String, Error> {
+        self.patch_runtime_config(&json!({ "tun": { "enable": enable } }))?;
+        let start = Instant::now();
+        loop {
+            let tun = self.runtime_config()?.tun;
+            if tun.enable == enable {
+                return Ok(if enable {
+                    format!("TUN enabled, stack: {}", tun.stack)
+                } else {
+                    "TUN disabled".to_string()
+                });
+            }
+            if start.elapsed() >= TUN_WAIT {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        if !enable {
+            return Err(Error::other("The core didn't disable TUN"));
+        }
+        let core = Path::new(&self.tui_cfg.clash_core_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let msg = match Utils::proc_has_net_admin(core) {
+            Some(false) => "Failed to enable TUN: the core lacks cap_net_admin, \
+                run SetPermission and restart the core",
+            Some(true) => "Failed to enable TUN, see the log of the core",
+            None => "Failed to enable TUN, the core might lack cap_net_admin: \
+                run SetPermission and restart the core, or see its log",
+        };
+        Err(Error::new(std::io::ErrorKind::PermissionDenied, msg))
+    }
+
+    /// Switch the stack of TUN, the core recreates the device if it's on
+    pub fn set_tun_stack(&self, stack: TunStack) -> Result<(), Error> {
+        // the core takes any case
+        let stack = stack.to_string().to_lowercase();
+        self.patch_runtime_config(&json!({ "tun": { "stack": stack } }))
+    }
+}
+
+fn patch_yaml_file(path: &Path, patch: &Value) -> Result<(), Error> {
+    let mut yaml = Utils::parse_yaml(path)?;
+    let Some(root) = yaml.as_mapping_mut() else {
+        return Err(Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} is not a mapping", path.display()),
+        ));
+    };
+    let Some(patch) = patch.as_object() else {
+        return Ok(());
+    };
+    for (key, value) in patch {
+        let value: serde_yaml::Value = serde_yaml::to_value(value).map_err(Error::other)?;
+        if key == "sniffing" {
+            // `sniffing` of the api is `sniffer.enable` in the file
+            let sniffer = root
+                .entry("sniffer".into())
+                .or_insert_with(|| serde_yaml::Mapping::new().into());
+            if let Some(sniffer) = sniffer.as_mapping_mut() {
+                sniffer.insert("enable".into(), value);
+            }
+        } else {
+            root.insert(key.as_str().into(), value);
+        }
+    }
+    let body = serde_yaml::to_string(&yaml).map_err(Error::other)?;
+    super::replace_file(path, body.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn runtime_config_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("runtime_config", &mock);
+        let util = dir.util();
+        let patch = serde_json::json!({"allow-lan": true, "mixed-port": 7891, "sniffing": true});
+        util.patch_runtime_config(&patch).unwrap();
+        let cfg = util.runtime_config().unwrap();
+        assert!(cfg.allow_lan && cfg.sniffing);
+        assert_eq!(cfg.mixed_port, 7891);
+
+        util.persist_runtime_config(&patch).unwrap();
+        let basic = crate::utils::parse_yaml(&dir.0.join(super::super::BASIC_FILE)).unwrap();
+        assert_eq!(basic["allow-lan"], serde_yaml::Value::Bool(true));
+        assert_eq!(basic["mixed-port"].as_u64(), Some(7891));
+        assert_eq!(basic["sniffer"]["enable"], serde_yaml::Value::Bool(true));
+        // the others are kept
+        assert_eq!(basic["secret"].as_str(), Some("test"));
+        // replaced through a temp file
+        assert!(!dir.0.join(format!(".{}.tmp", super::super::BASIC_FILE)).exists());
+    }
+    #[test]
+    fn tun_test() {
+        use crate::tui::tabs::ClashSrvOp;
+        let mock = MockController::start("test");
+        let dir = TestDir::new("tun", &mock);
+        let util = dir.util();
+        util.set_tun_stack(api::TunStack::Gvisor).unwrap();
+        assert!(util.clash_srv_ctl(ClashSrvOp::SwitchTun).unwrap().contains("gVisor"));
+        assert_eq!(mock.config()["tun"]["enable"], true);
+        let state = util.update_state(None, None);
+        assert!(matches!(state.tun, Some(api::TunStack::Gvisor)));
+
+        util.clash_srv_ctl(ClashSrvOp::SwitchTun).unwrap();
+        assert_eq!(mock.config()["tun"]["enable"], false);
+        // accepted but never up
+        mock.deny_tun();
+        let err = util.set_tun(true).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("cap_net_admin"));
+    }
+}
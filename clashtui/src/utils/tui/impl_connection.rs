@@ -0,0 +1,20 @@
+use super::ClashTuiUtil;
+use api::ClashConnections;
+use std::io::Error;
+use std::sync::mpsc::Sender;
+
+impl ClashTuiUtil {
+    /// Fetch connections in background, the result is sent to `tx`
+    pub fn fetch_connections(&self, tx: Sender<Result<ClashConnections, String>>) {
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(api.connections().map_err(|e| e.to_string()));
+        });
+    }
+    pub fn close_connection(&self, id: &str) -> Result<(), Error> {
+        self.clash_api.connection_close(id).map_err(Error::from)
+    }
+    pub fn close_all_connections(&self) -> Result<(), Error> {
+        self.clash_api.connection_close_all().map_err(Error::from)
+    }
+}
@@ -0,0 +1,61 @@
+use super::{impl_traffic::follow, ClashTuiUtil};
+use api::{ClashLog, ClashUtil, LogLevel};
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, TryRecvError},
+    Arc,
+};
+
+/// The logs followed in background, which stops once this is dropped
+///
+/// `None` is received every time the connection is lost
+pub struct CoreLogs {
+    rx: Receiver<Option<ClashLog>>,
+    // a quiet level might send nothing for long, don't wait for a failed send
+    stop: Arc<AtomicBool>,
+}
+impl CoreLogs {
+    pub fn try_recv(&self) -> Result<Option<ClashLog>, TryRecvError> {
+        self.rx.try_recv()
+    }
+}
+impl Drop for CoreLogs {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl ClashTuiUtil {
+    /// Follow the logs of the core at `level` and above
+    pub fn follow_core_logs(&self, level: LogLevel) -> CoreLogs {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        follow(
+            self.clash_api.clone(),
+            tx,
+            stop.clone(),
+            move |api: &ClashUtil| api.logs(level),
+            Some,
+            None,
+        );
+        CoreLogs { rx, stop }
+    }
+    /// Save `lines` to a new file in the app home, return its path
+    pub fn save_core_logs<'a, I>(&self, lines: I) -> std::io::Result<PathBuf>
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        let path = self.clashtui_dir.join(format!(
+            "mihomo_{}.log",
+            chrono::Local::now().format("%Y%m%d_%H%M%S")
+        ));
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        for line in lines {
+            writeln!(file, "{line}")?;
+        }
+        file.flush()?;
+        Ok(path)
+    }
+}
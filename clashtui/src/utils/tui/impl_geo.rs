@@ -0,0 +1,209 @@
+use super::ClashTuiUtil;
+use crate::utils::{str_bytes, str_duration};
+use api::ClashUtil;
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::SystemTime,
+};
+
+// Assets of MetaCubeX/meta-rules-dat the core knows about
+const GEO_FILES: [&str; 5] = [
+    "geoip.metadb",
+    "geosite.dat",
+    "geoip.dat",
+    "country.mmdb",
+    "GeoLite2-ASN.mmdb",
+];
+// Fetched when none of the above exists yet
+const DEFAULT_GEO_FILES: [&str; 2] = ["geoip.metadb", "geosite.dat"];
+
+/// Sent once a geo update in background is done, see [finish_geo_update][ClashTuiUtil::finish_geo_update]
+pub struct GeoUpdate {
+    res: Result<String, Error>,
+    // the release now in place, recorded on the ui thread
+    release_id: Option<String>,
+}
+
+impl ClashTuiUtil {
+    /// Size and age of the geo files in `clash_cfg_dir`
+    pub fn geo_data_status(&self) -> Vec<String> {
+        let now = SystemTime::now();
+        let mut lines: Vec<String> = self
+            .local_geo_files()
+            .into_iter()
+            .filter_map(|(_, path)| {
+                let meta = fs::metadata(&path).ok()?;
+                let age = meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| now.duration_since(t).ok())
+                    .map_or("Unknown".to_string(), str_duration);
+                Some(format!(
+                    "{}: {}, {}",
+                    path.file_name()?.to_string_lossy(),
+                    str_bytes(meta.len()),
+                    age
+                ))
+            })
+            .collect();
+        if lines.is_empty() {
+            lines.push(format!(
+                "No geo data found in {}",
+                self.tui_cfg.clash_cfg_dir
+            ));
+        }
+        let release = self.clashtui_data.borrow().geo_release_id.clone();
+        lines.push(String::new());
+        lines.push(format!(
+            "Installed release: {}",
+            if release.is_empty() { "Unknown" } else { release.as_str() }
+        ));
+        lines
+    }
+
+    /// The core downloads the geo data by itself with its own `geox-url`, in background
+    pub fn update_geo_data_by_core(&self) -> Receiver<GeoUpdate> {
+        let (tx, rx) = mpsc::channel();
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            let res = api
+                .config_geo_update()
+                .map(|_| "Geo data updated by the core".to_string())
+                .map_err(Error::from);
+            let _ = tx.send(GeoUpdate {
+                res,
+                release_id: None,
+            });
+        });
+        rx
+    }
+
+    /// Download the latest release of MetaCubeX/meta-rules-dat into `clash_cfg_dir`, in background
+    pub fn update_geo_data_from_github(&self) -> Receiver<GeoUpdate> {
+        let (tx, rx) = mpsc::channel();
+        let api = self.clash_api.clone();
+        let targets = self.local_geo_files();
+        let installed = self.clashtui_data.borrow().geo_release_id.clone();
+        std::thread::spawn(move || {
+            let (res, release_id) = match download_geo_data(&api, targets, &installed) {
+                Ok((msg, release_id)) => (Ok(msg), release_id),
+                Err(e) => (Err(e), None),
+            };
+            let _ = tx.send(GeoUpdate { res, release_id });
+        });
+        rx
+    }
+
+    /// Record what `update` installed, return what to show
+    pub fn finish_geo_update(&self, update: GeoUpdate) -> Result<String, Error> {
+        if let Some(id) = update.release_id {
+            self.clashtui_data.borrow_mut().geo_release_id = id;
+            self.save_to_data_file();
+        }
+        update.res
+    }
+
+    /// Pairs of (asset name, local path), keeping the file names already in use
+    fn local_geo_files(&self) -> Vec<(&'static str, PathBuf)> {
+        let dir = Path::new(&self.tui_cfg.clash_cfg_dir);
+        let existing: Vec<String> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let found: Vec<(&'static str, PathBuf)> = GEO_FILES
+            .iter()
+            .filter_map(|name| {
+                existing
+                    .iter()
+                    .find(|f| f.eq_ignore_ascii_case(name))
+                    .map(|f| (*name, dir.join(f)))
+            })
+            .collect();
+        if found.is_empty() {
+            DEFAULT_GEO_FILES
+                .iter()
+                .map(|name| (*name, dir.join(name)))
+                .collect()
+        } else {
+            found
+        }
+    }
+}
+
+/// Return what to show and the id of the release now in place, if it changed
+fn download_geo_data(
+    api: &ClashUtil,
+    targets: Vec<(&'static str, PathBuf)>,
+    installed: &str,
+) -> Result<(String, Option<String>), Error> {
+    let with_proxy = api.version().is_ok();
+    let release = api.geo_release(with_proxy)?;
+    if installed == release.id && targets.iter().all(|(_, p)| p.is_file()) {
+        return Ok((format!("Geo data is up to date ({})", release.tag_name), None));
+    }
+
+    let mut updated = vec![];
+    for (name, path) in targets {
+        let Some(asset) = release.assets.iter().find(|a| a.name == name) else {
+            log::warn!("Geo asset `{name}` not found in {}", release.tag_name);
+            continue;
+        };
+        download_to(api, asset.get_url(), with_proxy, &path)
+            .map_err(|e| Error::new(e.kind(), format!("{name}: {e}")))?;
+        updated.push(name);
+    }
+
+    Ok((
+        format!(
+            "Updated to {}: {}\nRestart the core to load them",
+            release.tag_name,
+            updated.join(", ")
+        ),
+        Some(release.id),
+    ))
+}
+
+/// Keep the old file if anything goes wrong
+fn download_to(api: &ClashUtil, url: &str, with_proxy: bool, path: &Path) -> Result<(), Error> {
+    let tmp = path.with_extension("clashtui_tmp");
+    let res = api
+        .download(url, with_proxy)
+        .map_err(Error::from)
+        .and_then(|resp| {
+            let mut f = fs::File::create(&tmp)?;
+            resp.copy_to(&mut f)
+        });
+    match res {
+        Ok(0) => Err(Error::other("Empty file")),
+        Ok(_) => fs::rename(&tmp, path),
+        Err(e) => Err(e),
+    }
+    .inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn geo_update_by_core_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("geo_by_core", &mock);
+        let util = dir.util();
+        let update = util.update_geo_data_by_core().recv().unwrap();
+        assert!(util.finish_geo_update(update).is_ok());
+        assert!(mock.hits().contains(&"POST /configs/geo".to_string()));
+        // nothing is recorded for the core
+        assert!(util.clashtui_data.borrow().geo_release_id.is_empty());
+    }
+}
@@ -1,14 +1,39 @@
 use crate::utils::tui::{NetProviderMap, ProfileType};
 
 use super::ClashTuiUtil;
-use crate::utils::{is_yaml, utils as Utils};
-use api::ProfileSectionType;
+use crate::utils::{is_yaml, str_bytes, utils as Utils};
+use api::{ClashUtil, ProfileSectionType, SubscriptionInfo};
 use std::{
     fs::{create_dir_all, File},
     io::Error,
     path::{Path, PathBuf},
 };
 
+// Warn once a subscription has used this much of its quota
+const QUOTA_WARN: f64 = 0.9;
+// or expires within these days
+const EXPIRY_WARN_DAYS: i64 = 7;
+// Hours between updates of profiles without `profile-update-interval`
+const DEFAULT_UPDATE_INTERVAL: u64 = 24;
+// Longer `profile-update-interval` are taken as a year
+const MAX_UPDATE_INTERVAL: f64 = 24.0 * 365.0;
+
+/// Headers of a subscription worth keeping
+#[derive(Default)]
+pub(super) struct ProfileHeaders {
+    pub subscription: Option<SubscriptionInfo>,
+    // hours
+    pub update_interval: Option<u64>,
+}
+impl ProfileHeaders {
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            subscription: self.subscription.or(other.subscription),
+            update_interval: self.update_interval.or(other.update_interval),
+        }
+    }
+}
+
 impl ClashTuiUtil {
     pub fn crt_yaml_with_template(&self, template_name: &String) -> Result<(), String> {
         use std::borrow::Cow;
@@ -254,6 +279,7 @@ impl ClashTuiUtil {
         if self.get_profile_type(profile_name).is_some_and(|t| t == ProfileType::Url) {
             let _ = remove_file(self.get_profile_cache_unchecked(profile_name));  // Not important
         }
+        self.clashtui_data.borrow_mut().profiles.remove(profile_name);
         remove_file(self.get_profile_path_unchecked(profile_name)).map_err(|e| e.to_string())
     }
 
@@ -276,7 +302,7 @@ impl ClashTuiUtil {
                 profile_name,
                 err.to_string()
             );
-            return Err(Error::new(std::io::ErrorKind::Other, err));
+            return Err(Error::other(err));
         };
         let body = serde_json::json!({
             "path": self.tui_cfg.clash_cfg_path.as_str(),
@@ -289,7 +315,7 @@ impl ClashTuiUtil {
                 profile_name,
                 err.to_string()
             );
-            return Err(Error::new(std::io::ErrorKind::Other, err));
+            return Err(Error::other(err));
         };
         Ok(())
     }
@@ -326,131 +352,101 @@ impl ClashTuiUtil {
 
         let final_clash_cfg_file = File::create(&self.tui_cfg.clash_cfg_path)?;
         serde_yaml::to_writer(final_clash_cfg_file, &dst_parsed_yaml)
-            .map_err(|e| Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| Error::other(e.to_string()))?;
 
         Ok(())
     }
 
-    pub fn update_profile(
-        &self,
-        profile_name: &String,
-        does_update_all: bool,
-    ) -> std::io::Result<Vec<String>> {
-        self.update_profile_with_clashtui(profile_name, does_update_all)
+    /// The `subscription-userinfo` of the last update of `profile_name`
+    pub fn subscription_info(&self, profile_name: &str) -> Option<SubscriptionInfo> {
+        self.clashtui_data
+            .borrow()
+            .profiles
+            .get(profile_name)
+            .and_then(|p| p.subscription)
+    }
+    /// Subscriptions near their quota or expiry
+    pub fn subscription_warnings(&self) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp();
+        self.get_profile_names()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|name| subscription_warning(&name, &self.subscription_info(&name)?, now))
+            .collect()
     }
 
-    // The advantage of using this interface for updates is that you can know the reason for update failures without needing to check mihomo's logs. The downside is that it requires resolving file permission issues.
-    pub fn update_profile_with_clashtui(
-        &self,
-        profile_name: &String,
-        does_update_all: bool,
-    ) -> std::io::Result<Vec<String>> {
-        let mut profile_yaml_path = self.profile_dir.join(profile_name);
-        let mut result = Vec::new();
-        if self.get_profile_type(profile_name)
-            .is_some_and(|t| t == ProfileType::Url)
-        {
-            let sub_url = self.extract_profile_url(profile_name)?;
-            profile_yaml_path = self.get_profile_cache_unchecked(profile_name);
-            // Update the file to keep up-to-date
-            self.download_profile(sub_url.as_str(), &profile_yaml_path)?;
-
-            result.push(format!("Updated: {}, {}", profile_name, sub_url));
-        }
-
-        let mut section_types = vec![ProfileSectionType::ProxyProvider];
-        if does_update_all {
-            section_types.push(ProfileSectionType::RuleProvider);
-        }
-
-        let mut net_providers = NetProviderMap::new();
-        if let Ok(providers) = self.extract_net_providers(&profile_yaml_path, &section_types) {
-            net_providers.extend(providers);
-        }
-
-        for (_, providers) in net_providers {
-            for (name, url, path) in providers {
-                match self.download_profile(&url, &Path::new(&self.tui_cfg.clash_cfg_dir).join(&path)) {
-                    Ok(_) => result.push(format!("Updated: {}, {}", name, url)),
-                    Err(e) => result.push(format!("Not updated: {}, {}, {}", name, url, e)),
-
-                }
+    /// Whether `profile_name` has waited for its update interval since the last update
+    ///
+    /// Profiles without anything to download are never due
+    pub fn is_profile_due(&self, profile_name: &str, now: i64) -> bool {
+        let (interval, last_update) = self
+            .clashtui_data
+            .borrow()
+            .profiles
+            .get(profile_name)
+            .map_or((None, None), |p| (p.update_interval, Some(p.last_update)));
+        let interval = interval.unwrap_or(match self.tui_cfg.profile_update_interval {
+            0 => DEFAULT_UPDATE_INTERVAL,
+            h => h,
+        });
+        let last_update = if let Some(last_update) = last_update {
+            // 0 if none of the updates succeeded
+            last_update
+        } else if self.get_profile_type(profile_name) == Some(ProfileType::Url) {
+            // updated before the time was recorded, the cache is written by every update
+            match self
+                .get_profile_yaml_path(profile_name)
+                .and_then(Utils::get_mtime)
+                .map(chrono::DateTime::<chrono::Utc>::from)
+            {
+                Ok(t) => t.timestamp(),
+                Err(_) => return true,
             }
-        }
-
-        Ok(result)
+        } else {
+            // the yaml is edited by the user, only the providers are downloaded
+            self.oldest_provider_mtime(profile_name).unwrap_or(0)
+        };
+        let interval = i64::try_from(interval.saturating_mul(60 * 60)).unwrap_or(i64::MAX);
+        now.saturating_sub(last_update) >= interval && self.is_remote_profile(profile_name)
     }
-
-    fn download_profile(&self, url: &str, path: &PathBuf) -> std::io::Result<()> {
-        let directory = path
-            .parent()
-            .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Invalid file path"))?;
-        if !directory.exists() {
-            create_dir_all(directory)?;
+    /// Profiles to update, see [is_profile_due][Self::is_profile_due]
+    pub fn due_profiles(&self) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp();
+        self.get_profile_names()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|name| self.is_profile_due(name, now))
+            .collect()
+    }
+    /// `None` if any proxy-provider of a local profile is missing
+    fn oldest_provider_mtime(&self, profile_name: &str) -> Option<i64> {
+        let providers = self
+            .extract_net_providers(
+                &self.get_profile_path_unchecked(profile_name),
+                &[ProfileSectionType::ProxyProvider],
+            )
+            .ok()?;
+        let mut oldest = i64::MAX;
+        for (_, _, path) in providers.into_values().flatten() {
+            let mtime = Utils::get_mtime(Path::new(&self.tui_cfg.clash_cfg_dir).join(path)).ok()?;
+            oldest = oldest.min(chrono::DateTime::<chrono::Utc>::from(mtime).timestamp());
         }
-
-        let response = self.dl_remote_profile(url)?;
-        let mut output_file = File::create(path)?;      // will truncate the file
-        response.copy_to(&mut output_file)?;
-        Ok(())
+        Some(oldest)
     }
-
-    pub fn extract_net_providers(&self, profile_yaml_path: &PathBuf, provider_types: &Vec<ProfileSectionType>) -> std::io::Result<NetProviderMap> {
-        let yaml_content = std::fs::read_to_string(&profile_yaml_path)?;
-        let parsed_yaml = match serde_yaml::from_str::<serde_yaml::Value>(&yaml_content) {
-            Ok(value) => value,
-            Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
-        };
-
-        let provider_keys: Vec<_> = provider_types.iter().filter_map(|s_type| {
-            match s_type {
-                ProfileSectionType::ProxyProvider => Some("proxy-providers"),
-                ProfileSectionType::RuleProvider => Some("rule-providers"),
-                _ => None,
-            }
-        }).collect();
-
-        let mut net_providers = NetProviderMap::new();
-        for section_key in provider_keys {
-            let section_val = if let Some(val) = parsed_yaml.get(section_key) {
-                val
-            } else {
-                continue;
-            };
-
-            let the_section_val = if let serde_yaml::Value::Mapping(val) = section_val {
-                val
-            } else {
-                continue;
-            };
-
-            let mut providers: Vec<(String, String, String)> = Vec::new();
-            for (provider_key, provider_val) in the_section_val {
-                let provider = if let Some(val) = provider_val.as_mapping() {
-                    val
-                } else {
-                    continue;
-                };
-
-                if let (Some(name), Some(url), Some(path)) = (
-                    Some(provider_key),
-                    provider.get(&serde_yaml::Value::String("url".to_string())),
-                    provider.get(&serde_yaml::Value::String("path".to_string())),
-                ) {
-                    if let (serde_yaml::Value::String(name), serde_yaml::Value::String(url), serde_yaml::Value::String(path)) = (name, url, path) {
-                        providers.push((name.clone(), url.clone(), path.clone()));
-                    }
-                }
-            }
-
-            if section_key == "proxy-providers" {
-                net_providers.insert(ProfileSectionType::ProxyProvider, providers);
-            } else if section_key == "rule-providers" {
-                net_providers.insert(ProfileSectionType::RuleProvider, providers);
-            }
+    /// A subscription, or a profile with proxy-providers
+    fn is_remote_profile(&self, profile_name: &str) -> bool {
+        if self.get_profile_type(profile_name) == Some(ProfileType::Url) {
+            return true;
         }
+        self.extract_net_providers(
+            &self.get_profile_path_unchecked(profile_name),
+            &[ProfileSectionType::ProxyProvider],
+        )
+        .is_ok_and(|providers| providers.values().any(|p| !p.is_empty()))
+    }
 
-        Ok(net_providers)
+    pub fn extract_net_providers(&self, profile_yaml_path: &Path, provider_types: &[ProfileSectionType]) -> std::io::Result<NetProviderMap> {
+        extract_net_providers(profile_yaml_path, provider_types)
     }
 
     // Check if need to correct perms of files in clash_cfg_dir. If perm is incorrect return false.
@@ -475,123 +471,16 @@ impl ClashTuiUtil {
             if let Some(dir_group) = 
                 nix::unistd::Group::from_gid(nix::unistd::Gid::from_raw(metadata.gid())).unwrap()
             {
-                if  Utils::find_files_not_in_group(&dir.to_path_buf(), dir_group.name.as_str()).len() > 0
-                    || Utils::find_files_not_group_writable(&dir.to_path_buf()).len() > 0
+                if  !Utils::find_files_not_in_group(&dir.to_path_buf(), dir_group.name.as_str()).is_empty()
+                    || !Utils::find_files_not_group_writable(&dir.to_path_buf()).is_empty()
                     {
                         return false;
                     }
             }
         }
 
-        return true;
+        true
     }
-
-    /*** Update profile with api
-    // format: {type, [(name, result)]}
-    pub type UpdateProviderType = std::collections::HashMap<ProfileSectionType, Vec<(String, std::io::Result<String>)>>;
-
-    // Using api update, the user needs to check the logs to understand why the updates failed. The success rate of my testing updates is not as high as using clashtui.
-    pub fn update_profile_with_api(
-        &self,
-        profile_name: &String,
-        does_update_all: bool,
-    ) -> std::io::Result<Vec<String>> {
-
-        let mut result = Vec::new();
-        if self.get_profile_type(profile_name)
-            .is_some_and(|t| t == ProfileType::Url)
-        {
-            let sub_url = self.extract_profile_url(profile_name)?;
-            let profile_yaml_path = self.get_profile_cache_unchecked(profile_name);
-            // Update the file to keep up-to-date
-            self.download_profile(sub_url.as_str(), &profile_yaml_path)?;
-
-            result.push(
-                format!("Updated: {}, {}", profile_name, sub_url)
-            );
-        }
-
-        let mut provider_types = vec![ProfileSectionType::ProxyProvider];
-        if does_update_all {
-            provider_types.push(ProfileSectionType::RuleProvider);
-        }
-
-        let mut update_providers_result = UpdateProviderType::new();
-        let mut update_times = ProfileTimeMap::new();
-        for t in provider_types {
-            // Get result of update providers
-            update_providers_result.insert(t, self.clash_api.update_providers(t)?);
-
-            // Get update times after update providers
-            if let Ok(name_times) = self.clash_api.extract_provider_utimes_with_api(t) {
-                update_times.insert(t, name_times);
-            }
-        }
-
-        // Add results of updating providers
-        for (section_type, res) in update_providers_result {
-            for (name, r) in res {
-                // Generate duration_str
-                let duration_str = if let Ok(d) = Self::cal_mtime_duration(&update_times, section_type, &name) {
-                    Utils::str_duration(d)
-                } else {
-                    "No update times or can't cal the duration".to_string()
-                };
-
-                let line = match r {
-                    Ok(_) => {
-                        format!("Sent update request: {}, duration = '{}'", name, duration_str)
-                    }
-                    Err(err) => {
-                        log::error!("Not Sent update request:{err}");
-                        format!("Not Sent update request: {}, duration = '{}'", name, duration_str)
-                    }
-                };
-                result.push(line);
-            }
-        }
-
-        Ok(result)
-    }
-
-    // duration: now - mtime
-    fn cal_mtime_duration(mtimes: &ProfileTimeMap, section_type: ProfileSectionType, name: &String) -> std::io::Result<std::time::Duration> {
-        let mt = Self::extract_the_mtime(mtimes, section_type, name).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "No the mtime in mtimes"))?;
-        let now = std::time::SystemTime::now();
-        now.duration_since(mt).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-    }
-
-    fn extract_the_mtime<'a>(mtimes: &'a ProfileTimeMap, section_type: ProfileSectionType, name: &String) -> &'a Option<std::time::SystemTime> {
-        if let Some(times) = mtimes.get(&section_type) {
-            for (n, the_mtime) in times {
-                if n == name {
-                    return the_mtime;
-                }
-            }
-        }
-
-        &None
-    }
-
-    // For Showing provider duration (now - mtime) in `update_profile_with_api`.
-    // format: {type: [(name, modifytime)]}
-    pub type ProfileTimeMap = std::collections::HashMap<ProfileSectionType, Vec<(String, Option<std::time::SystemTime>)>>;
-    fn get_provider_mtime(&self, section_types: Vec<ProfileSectionType>, profile_yaml_path: &PathBuf) -> std::io::Result<ProfileTimeMap> {
-        let mut modify_info = ProfileTimeMap::new();
-        if let Ok(net_res) = self.extract_net_providers(profile_yaml_path, &section_types) {
-            for (key, res) in net_res {
-                let name_and_times = res.into_iter().map(|(name, _, path)| {
-                    let clash_cfg_dir = Path::new(&self.tui_cfg.clash_cfg_dir);
-                    let time = Utils::get_mtime(clash_cfg_dir.join(path)).ok();
-                    (name, time)
-                }).collect();
-                modify_info.insert(key, name_and_times);
-            }
-        }
-
-        Ok(modify_info)
-    }
-    ***/
 }
 
 impl ClashTuiUtil {
@@ -632,7 +521,7 @@ impl ClashTuiUtil {
             "No valid yaml file",
         ))
     }
-    fn get_profile_cache_unchecked<P>(&self, profile_name: P) -> PathBuf
+    pub(super) fn get_profile_cache_unchecked<P>(&self, profile_name: P) -> PathBuf
     where
         P: AsRef<Path> + AsRef<std::ffi::OsStr>,
     {
@@ -675,7 +564,7 @@ impl ClashTuiUtil {
             let line = line.trim();
 
             if !line.starts_with("#") {     // `#` is comment char
-                if url_regex.is_match(&line) {
+                if url_regex.is_match(line) {
                     return Ok(line.to_string());
                 }
             }
@@ -684,6 +573,193 @@ impl ClashTuiUtil {
         Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("No URL found in {}", profile_name)))
     }
 }
+/// e.g. `12.3GB/100.0GB, 21d left`
+pub fn str_subscription(info: &SubscriptionInfo, now: i64) -> String {
+    let mut s = if info.total > 0 {
+        format!("{}/{}", str_bytes(info.used()), str_bytes(info.total))
+    } else {
+        format!("{} used", str_bytes(info.used()))
+    };
+    match info.days_left(now) {
+        Some(d) if d < 0 => s.push_str(", expired"),
+        Some(d) => s.push_str(&format!(", {d}d left")),
+        None => (),
+    }
+    s
+}
+
+pub(super) fn subscription_warning(name: &str, info: &SubscriptionInfo, now: i64) -> Option<String> {
+    match info.days_left(now) {
+        Some(d) if d < 0 => return Some(format!("Subscription `{name}` has expired")),
+        Some(0) => return Some(format!("Subscription `{name}` expires within a day")),
+        Some(d) if d < EXPIRY_WARN_DAYS => {
+            return Some(format!("Subscription `{name}` expires in {d} days"))
+        }
+        _ => (),
+    }
+    match info.usage() {
+        Some(u) if u >= 1.0 => Some(format!("Subscription `{name}` is out of quota")),
+        Some(u) if u >= QUOTA_WARN => Some(format!(
+            "Subscription `{name}` has used {:.0}% of its quota",
+            u * 100.0
+        )),
+        _ => None,
+    }
+}
+
+/// Download `url` to `path` if it looks like a `kind`, headers of the subscription are returned
+///
+/// The body goes to a temp file which replaces `path` once checked, so `path` keeps the
+/// previous version if anything fails.
+pub(super) fn download_profile(
+    api: &ClashUtil,
+    with_proxy: bool,
+    url: &str,
+    path: &Path,
+    kind: ProfileSectionType,
+) -> std::io::Result<ProfileHeaders> {
+    let directory = path
+        .parent()
+        .ok_or_else(|| Error::new(std::io::ErrorKind::NotFound, "Invalid file path"))?;
+    if !directory.exists() {
+        create_dir_all(directory)?;
+    }
+
+    // an error page is not a profile
+    let response = api.download(url, with_proxy)?;
+    let headers = ProfileHeaders {
+        subscription: response
+            .header("subscription-userinfo")
+            .and_then(SubscriptionInfo::parse),
+        // in hours, might be a float
+        update_interval: response
+            .header("profile-update-interval")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|h| *h > 0.0)
+            .map(|h| h.min(MAX_UPDATE_INTERVAL).ceil() as u64),
+    };
+    let mut body = Vec::new();
+    response.copy_to(&mut body)?;
+    check_payload(kind, path, &body)?;
+
+    super::replace_file(path, &body)?;
+    Ok(headers)
+}
+
+/// Whether `body` is worth saving to `path` as a `kind`
+///
+/// Profiles must be a yaml mapping. Providers might be in other formats, they just
+/// can't be an html page, or yaml without what the core looks for.
+fn check_payload(kind: ProfileSectionType, path: &Path, body: &[u8]) -> std::io::Result<()> {
+    let invalid = |msg: &str| Err(Error::new(std::io::ErrorKind::InvalidData, msg.to_string()));
+    let text = String::from_utf8_lossy(body);
+    let text = text.trim_start_matches('\u{feff}').trim();
+    if text.is_empty() {
+        return invalid("Empty download");
+    }
+    let head = text.chars().take(64).collect::<String>().to_ascii_lowercase();
+    if head.starts_with("<!doctype") || head.starts_with("<html") {
+        return invalid("Got an html page");
+    }
+    let mapping = serde_yaml::from_str::<serde_yaml::Value>(text)
+        .ok()
+        .and_then(|v| match v {
+            serde_yaml::Value::Mapping(m) => Some(m),
+            _ => None,
+        });
+    let is_yaml_path = path
+        .extension()
+        .is_some_and(|e| e == "yaml" || e == "yml");
+    match kind {
+        ProfileSectionType::Profile if mapping.is_none() => invalid("Not a yaml mapping"),
+        ProfileSectionType::Profile => Ok(()),
+        // base64 or links of a subscription are converted by the core
+        ProfileSectionType::ProxyProvider => {
+            let is_links = |text: &str| {
+                text.lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .all(|l| l.contains("://"))
+            };
+            // e.g. a `Forbidden` reply is valid base64 as well
+            let is_base64 = || {
+                api::base64_decode(text)
+                    .and_then(|links| String::from_utf8(links).ok())
+                    .is_some_and(|links| links.contains("://") && is_links(&links))
+            };
+            if mapping.is_some_and(|m| m.contains_key("proxies")) || is_links(text) || is_base64() {
+                Ok(())
+            } else {
+                invalid("No proxies found")
+            }
+        }
+        // `format: text` or `mrs` might be saved as anything else
+        ProfileSectionType::RuleProvider
+            if is_yaml_path && !mapping.is_some_and(|m| m.contains_key("payload")) =>
+        {
+            invalid("No payload found")
+        }
+        ProfileSectionType::RuleProvider => Ok(()),
+    }
+}
+
+pub(super) fn extract_net_providers(profile_yaml_path: &Path, provider_types: &[ProfileSectionType]) -> std::io::Result<NetProviderMap> {
+    let yaml_content = std::fs::read_to_string(profile_yaml_path)?;
+    let parsed_yaml = match serde_yaml::from_str::<serde_yaml::Value>(&yaml_content) {
+        Ok(value) => value,
+        Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+    };
+
+    let provider_keys: Vec<_> = provider_types.iter().filter_map(|s_type| {
+        match s_type {
+            ProfileSectionType::ProxyProvider => Some("proxy-providers"),
+            ProfileSectionType::RuleProvider => Some("rule-providers"),
+            _ => None,
+        }
+    }).collect();
+
+    let mut net_providers = NetProviderMap::new();
+    for section_key in provider_keys {
+        let section_val = if let Some(val) = parsed_yaml.get(section_key) {
+            val
+        } else {
+            continue;
+        };
+
+        let the_section_val = if let serde_yaml::Value::Mapping(val) = section_val {
+            val
+        } else {
+            continue;
+        };
+
+        let mut providers: Vec<(String, String, String)> = Vec::new();
+        for (provider_key, provider_val) in the_section_val {
+            let provider = if let Some(val) = provider_val.as_mapping() {
+                val
+            } else {
+                continue;
+            };
+
+            if let (Some(name), Some(url), Some(path)) = (
+                Some(provider_key),
+                provider.get(serde_yaml::Value::String("url".to_string())),
+                provider.get(serde_yaml::Value::String("path".to_string())),
+            ) {
+                if let (serde_yaml::Value::String(name), serde_yaml::Value::String(url), serde_yaml::Value::String(path)) = (name, url, path) {
+                    providers.push((name.clone(), url.clone(), path.clone()));
+                }
+            }
+        }
+
+        if section_key == "proxy-providers" {
+            net_providers.insert(ProfileSectionType::ProxyProvider, providers);
+        } else if section_key == "rule-providers" {
+            net_providers.insert(ProfileSectionType::RuleProvider, providers);
+        }
+    }
+
+    Ok(net_providers)
+}
+
 /// # Limitations
 ///
 /// Windows treats symlink creation as a [privileged action][symlink-security],
@@ -702,29 +778,192 @@ fn crt_symlink_file<P: AsRef<std::path::Path>>(original: P, target: P) -> std::i
 #[cfg(test)]
 mod tests {
     use super::*;
-    fn sym() -> ClashTuiUtil {
-        let exe_dir = std::env::current_dir().unwrap();
-        println!("{exe_dir:?}");
-        let clashtui_dir = exe_dir.parent().unwrap().join("Example");
-        let (util, _) = ClashTuiUtil::new(
-            &clashtui_dir.to_path_buf(),
-            true
-        );
-        util
-    }
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
 
     #[test]
     fn test_extrat_profile_net_res() {
-        let sym = sym();
+        let mock = MockController::start("test");
+        let dir = TestDir::new("net_res", &mock);
+        let profile = format!(
+            "proxy-providers:\n  a:\n    url: {0}/a\n    path: ./a.yaml\n  local:\n    type: file\n    path: ./local.yaml\n",
+            mock.url()
+        );
+        mock.serve("/sub", 200, &[], profile);
+        mock.serve("/a", 200, &[], "proxies: []\n");
+        std::fs::write(dir.0.join("profiles/profile1"), format!("{}/sub\n", mock.url())).unwrap();
+        let sym = dir.util();
+        sym.update_profile("profile1", false).unwrap();
 
-        let profile_name = "profile1.yaml";
+        let profile_name = "profile1";
         let mut profile_yaml_path = sym.profile_dir.join(profile_name);
         if sym.get_profile_type(profile_name)
             .is_some_and(|t| t == ProfileType::Url)
         {
             profile_yaml_path = sym.get_profile_cache_unchecked(profile_name);
         }
-        let _ = sym.extract_net_providers(&profile_yaml_path, &vec![ProfileSectionType::ProxyProvider]);
+        let providers = sym
+            .extract_net_providers(&profile_yaml_path, &[ProfileSectionType::ProxyProvider])
+            .unwrap();
+        // `local` has no url
+        assert_eq!(
+            providers[&ProfileSectionType::ProxyProvider],
+            [("a".to_string(), format!("{}/a", mock.url()), "./a.yaml".to_string())]
+        );
     }
 
+    #[test]
+    fn check_payload_test() {
+        let yaml = Path::new("a.yaml");
+        let html = b"<!DOCTYPE html><html><body>502 Bad Gateway</body></html>";
+        for kind in [
+            ProfileSectionType::Profile,
+            ProfileSectionType::ProxyProvider,
+            ProfileSectionType::RuleProvider,
+        ] {
+            assert!(check_payload(kind, yaml, html).is_err());
+            assert!(check_payload(kind, yaml, b" \n").is_err());
+        }
+        assert!(check_payload(ProfileSectionType::Profile, yaml, b"proxies: []\n").is_ok());
+        assert!(check_payload(ProfileSectionType::Profile, yaml, b"- a\n- b\n").is_err());
+        // byte 64 is in the middle of a char
+        let zh = format!("# a{}\nproxies: []\n", "订阅".repeat(20));
+        assert!(check_payload(ProfileSectionType::Profile, yaml, zh.as_bytes()).is_ok());
+        assert!(check_payload(ProfileSectionType::Profile, yaml, "订阅已过期".repeat(20).as_bytes()).is_err());
+
+        let proxy = ProfileSectionType::ProxyProvider;
+        assert!(check_payload(proxy, yaml, b"proxies: []\n").is_ok());
+        assert!(check_payload(proxy, yaml, b"ss://YWVzOnBhc3M@1.2.3.4:8388#a\ntrojan://p@h:443\n").is_ok());
+        assert!(check_payload(proxy, yaml, b"c3M6Ly9ZV1Z6T25CaGMzTUAxLjIuMy40Ojg4ODg=\n").is_ok());
+        assert!(check_payload(proxy, yaml, b"rules: []\n").is_err());
+        assert!(check_payload(proxy, yaml, b"Forbidden").is_err());
+        assert!(check_payload(proxy, yaml, b"error token\n").is_err());
+
+        let rule = ProfileSectionType::RuleProvider;
+        assert!(check_payload(rule, yaml, b"payload:\n  - DOMAIN,a.com\n").is_ok());
+        assert!(check_payload(rule, yaml, b"DOMAIN,a.com\n").is_err());
+        assert!(check_payload(rule, Path::new("a.txt"), b"DOMAIN,a.com\n").is_ok());
+    }
+    #[test]
+    fn subscription_info_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("subscription", &mock);
+        let expire = chrono::Utc::now().timestamp() + 2 * 24 * 60 * 60 + 60;
+        let userinfo = format!("upload=1024; download=2048; total=1048576; expire={expire}");
+        mock.serve("/sub", 200, &[("subscription-userinfo", &userinfo)], "proxies: []\n");
+        std::fs::write(dir.0.join("profiles/sub"), format!("{}/sub\n", mock.url())).unwrap();
+
+        let util = dir.util();
+        let msg = util.update_profile("sub", false).unwrap();
+        assert!(msg.contains(&"Subscription: 3.0KB/1.0MB, 2d left".to_string()));
+        assert_eq!(util.subscription_info("sub").unwrap().used(), 3072);
+        // kept in the data file
+        assert_eq!(
+            dir.util().subscription_warnings(),
+            ["Subscription `sub` expires in 2 days"]
+        );
+        util.rmf_profile(&"sub".to_string()).unwrap();
+        assert!(util.subscription_info("sub").is_none());
+    }
+    #[test]
+    fn profile_due_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("profile_due", &mock);
+        mock.serve("/sub", 200, &[("profile-update-interval", "12")], "proxies: []\n");
+        std::fs::write(dir.0.join("profiles/sub"), format!("{}/sub\n", mock.url())).unwrap();
+        std::fs::write(dir.0.join("profiles/local.yaml"), "proxies: []\n").unwrap();
+
+        let util = dir.util();
+        let now = chrono::Utc::now().timestamp();
+        // never downloaded
+        assert!(util.is_profile_due("sub", now));
+        // nothing to download
+        assert!(!util.is_profile_due("local.yaml", now));
+        assert_eq!(util.due_profiles(), ["sub"]);
+
+        util.update_profile("sub", false).unwrap();
+        let util = dir.util();
+        assert!(!util.is_profile_due("sub", now));
+        assert!(util.is_profile_due("sub", now + 12 * 60 * 60 + 1));
+        assert!(util.due_profiles().is_empty());
+
+        // way too long for the seconds to fit
+        mock.serve("/sub", 200, &[("profile-update-interval", "1e30")], "proxies: []\n");
+        util.update_profile("sub", false).unwrap();
+        assert!(!util.is_profile_due("sub", now + 300 * 24 * 60 * 60));
+        assert!(util.is_profile_due("sub", now + 366 * 24 * 60 * 60));
+
+        // a local profile goes by its providers, not by when it was edited
+        std::fs::write(
+            dir.0.join("profiles/providers.yaml"),
+            format!("proxy-providers:\n  a:\n    url: {}/a\n    path: ./a.yaml\n", mock.url()),
+        )
+        .unwrap();
+        assert!(util.is_profile_due("providers.yaml", now));
+        let provider = std::fs::File::create(dir.0.join("a.yaml")).unwrap();
+        assert!(!util.is_profile_due("providers.yaml", now));
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(25 * 60 * 60);
+        provider.set_modified(old).unwrap();
+        assert!(util.is_profile_due("providers.yaml", now));
+    }
+    #[test]
+    fn failed_download_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("failed_download", &mock);
+        let profile = format!(
+            "proxy-providers:\n  a:\n    url: {}/a\n    path: ./a.yaml\n",
+            mock.url()
+        );
+        mock.serve("/sub", 200, &[], profile.clone());
+        mock.serve("/a", 500, &[], "Internal Server Error");
+        std::fs::write(dir.0.join("profiles/sub"), format!("{}/sub\n", mock.url())).unwrap();
+        let util = dir.util();
+        util.update_profile("sub", false).unwrap();
+        // retried before the interval is over
+        assert!(util.is_profile_due("sub", chrono::Utc::now().timestamp()));
+        mock.serve("/a", 200, &[], "proxies: []\n");
+        util.update_profile("sub", false).unwrap();
+        assert!(!util.is_profile_due("sub", chrono::Utc::now().timestamp()));
+        let cache = util.get_profile_yaml_path("sub").unwrap();
+
+        // the previous versions are kept
+        mock.serve("/a", 200, &[], "<!DOCTYPE html><html>Blocked</html>");
+        let report = util.update_profile("sub", false).unwrap();
+        assert!(report[1].starts_with("Not updated: a,"), "{report:?}");
+        assert_eq!(std::fs::read_to_string(dir.0.join("a.yaml")).unwrap(), "proxies: []\n");
+        mock.serve("/sub", 404, &[], "Not Found");
+        assert!(util.update_profile("sub", false).is_err());
+        assert_eq!(std::fs::read_to_string(&cache).unwrap(), profile);
+        // no temp files are left
+        assert!(!dir.0.join(".a.yaml.tmp").exists());
+        assert_eq!(std::fs::read_dir(cache.parent().unwrap()).unwrap().count(), 1);
+    }
+    #[test]
+    fn kept_group_test() {
+        use nix::unistd::{chown, getegid, getgroups, Gid, Uid};
+        use std::os::unix::fs::MetadataExt;
+        // a group other than the one new files get
+        let gid = if Uid::effective().is_root() {
+            Gid::from_raw(65534)
+        } else if let Some(gid) = getgroups().unwrap().into_iter().find(|g| *g != getegid()) {
+            gid
+        } else {
+            return;
+        };
+        let mock = MockController::start("test");
+        let dir = TestDir::new("kept_group", &mock);
+        mock.serve("/a", 200, &[], "proxies: []\n");
+        std::fs::write(
+            dir.0.join("profiles/sub"),
+            format!("proxy-providers:\n  a:\n    url: {}/a\n    path: ./a.yaml\n", mock.url()),
+        )
+        .unwrap();
+        let provider = dir.0.join("a.yaml");
+        std::fs::write(&provider, "").unwrap();
+        chown(&provider, None, Some(gid)).unwrap();
+
+        dir.util().update_profile("sub", false).unwrap();
+        assert_eq!(std::fs::read_to_string(&provider).unwrap(), "proxies: []\n");
+        assert_eq!(std::fs::metadata(&provider).unwrap().gid(), gid.as_raw());
+    }
 }
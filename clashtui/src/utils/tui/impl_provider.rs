@@ -0,0 +1,87 @@
+use super::ClashTuiUtil;
+use api::{ClashProvider, ClashUtil, ProfileSectionType};
+use std::io::Error;
+use std::sync::mpsc::Sender;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProviderOp {
+    Update,
+    HealthCheck,
+}
+
+/// (type, name, the reason of failure if any)
+pub type ProviderResult = (ProfileSectionType, String, Result<(), String>);
+
+impl ClashTuiUtil {
+    /// Proxy providers then rule providers, both sorted by name, fetched in background
+    ///
+    /// The `Compatible` one holding the proxies of the profile itself is skipped
+    pub fn fetch_providers(&self, tx: Sender<Result<Vec<ClashProvider>, String>>) {
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(fetch_providers(&api).map_err(|e| e.to_string()));
+        });
+    }
+    /// Apply `op` on `targets` one by one in background, one result per target is sent to `tx`
+    pub fn run_provider_op(
+        &self,
+        op: ProviderOp,
+        targets: Vec<(ProfileSectionType, String)>,
+        tx: Sender<ProviderResult>,
+    ) {
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            for (t, name) in targets {
+                let res = match op {
+                    ProviderOp::Update => api.provider_update(t, &name),
+                    ProviderOp::HealthCheck => api.provider_healthcheck(&name),
+                }
+                .map_err(|e| {
+                    log::warn!("Provider `{name}` => {e}");
+                    e.to_string()
+                });
+                if tx.send((t, name, res)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+fn fetch_providers(api: &ClashUtil) -> Result<Vec<ClashProvider>, Error> {
+    let mut result = Vec::new();
+    for t in [ProfileSectionType::ProxyProvider, ProfileSectionType::RuleProvider] {
+        let mut providers: Vec<ClashProvider> = api
+            .providers(t)?
+            .providers
+            .into_values()
+            .filter(|p| p.vehicle_type != "Compatible")
+            .collect();
+        providers.sort_by(|a, b| a.name.cmp(&b.name));
+        result.extend(providers);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn providers_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("providers", &mock);
+        let (tx, rx) = std::sync::mpsc::channel();
+        dir.util().fetch_providers(tx);
+        let names: Vec<String> = rx
+            .recv()
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        // `Compatible` is skipped
+        assert_eq!(names, ["sub", "reject"]);
+    }
+}
@@ -0,0 +1,85 @@
+use super::ClashTuiUtil;
+use api::ClashProxies;
+use std::io::Error;
+use std::sync::mpsc::Sender;
+
+const DEFAULT_DELAY_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+const DEFAULT_DELAY_TEST_TIMEOUT: u64 = 5000;
+
+/// (name, delay in ms or the reason of failure)
+pub type DelayResult = (String, Result<u64, String>);
+
+impl ClashTuiUtil {
+    /// Fetched in background, the result is sent to `tx`
+    pub fn fetch_proxies(&self, tx: Sender<Result<ClashProxies, String>>) {
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(api.proxies().map_err(|e| e.to_string()));
+        });
+    }
+    pub fn select_proxy(&self, group: &str, name: &str) -> Result<(), Error> {
+        self.clash_api
+            .proxy_select(group, name)
+            .map_err(Error::from)
+    }
+
+    fn delay_test_args(&self) -> (String, u64) {
+        let url = if self.tui_cfg.delay_test_url.is_empty() {
+            DEFAULT_DELAY_TEST_URL.to_string()
+        } else {
+            self.tui_cfg.delay_test_url.clone()
+        };
+        let timeout = if self.tui_cfg.delay_test_timeout == 0 {
+            DEFAULT_DELAY_TEST_TIMEOUT
+        } else {
+            self.tui_cfg.delay_test_timeout
+        };
+        (url, timeout)
+    }
+    /// Test `name` in background, the result is sent to `tx`
+    pub fn test_proxy_delay(&self, name: String, tx: Sender<DelayResult>) {
+        let api = self.clash_api.clone();
+        let (url, timeout) = self.delay_test_args();
+        std::thread::spawn(move || {
+            let res = api
+                .proxy_delay(&name, &url, timeout)
+                .map_err(|e| e.to_string());
+            let _ = tx.send((name, res));
+        });
+    }
+    /// Test all `members` of `group` in background, one result per member is sent to `tx`
+    pub fn test_group_delay(&self, group: String, members: Vec<String>, tx: Sender<DelayResult>) {
+        let api = self.clash_api.clone();
+        let (url, timeout) = self.delay_test_args();
+        std::thread::spawn(move || match api.group_delay(&group, &url, timeout) {
+            Ok(delays) => members.into_iter().for_each(|name| {
+                let res = delays.get(&name).copied().ok_or("Timeout".to_string());
+                let _ = tx.send((name, res));
+            }),
+            Err(e) => {
+                log::warn!("Test `{group}` => {e}");
+                members.into_iter().for_each(|name| {
+                    let _ = tx.send((name, Err(e.to_string())));
+                })
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn proxies_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("proxies", &mock);
+        let util = dir.util();
+        let (tx, rx) = std::sync::mpsc::channel();
+        util.fetch_proxies(tx);
+        assert!(rx.recv().unwrap().unwrap().get("node-a").is_some());
+        util.select_proxy("GLOBAL", "Proxy").unwrap();
+        assert_eq!(mock.selected("GLOBAL").as_deref(), Some("Proxy"));
+    }
+}
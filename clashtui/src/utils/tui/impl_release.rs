@@ -0,0 +1,154 @@
+use super::ClashTuiUtil;
+use crate::utils::LatestRelease;
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver},
+};
+
+const CLASHTUI_RELEASE: &str = "https://api.github.com/repos/JohanChane/clashtui/releases/latest";
+// Seconds between two checks
+const CHECK_INTERVAL: i64 = 24 * 60 * 60;
+
+impl ClashTuiUtil {
+    /// The cached result of [check_release][Self::check_release]
+    pub fn latest_release(&self) -> LatestRelease {
+        self.clashtui_data.borrow().latest_release.clone()
+    }
+
+    /// Fetch the latest release of clashtui in background, `None` if the cache is fresh
+    ///
+    /// The binary for this machine is downloaded into `release_stage_dir` if it's newer,
+    /// and it matches the `checksums.txt` of the release.
+    /// Pass the result to [release_checked][Self::release_checked].
+    pub fn check_release(&self) -> Option<Receiver<Result<LatestRelease, String>>> {
+        let now = chrono::Utc::now().timestamp();
+        let cached = self.latest_release();
+        if (0..CHECK_INTERVAL).contains(&(now - cached.checked_at)) {
+            return None;
+        }
+        let url = if self.tui_cfg.release_url.is_empty() {
+            CLASHTUI_RELEASE.to_string()
+        } else {
+            self.tui_cfg.release_url.clone()
+        };
+        let stage_dir = self.tui_cfg.release_stage_dir.clone();
+        let api = self.clash_api.clone();
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let with_proxy = api.version().is_ok();
+            let release = match api.github_release(&url, with_proxy) {
+                Ok(r) => r,
+                Err(e) => {
+                    log::warn!("Checking the release of clashtui => {e}");
+                    let _ = tx.send(Err(e.to_string()));
+                    return;
+                }
+            };
+            let mut latest = LatestRelease {
+                tag_name: release.tag_name.clone(),
+                changelog: release.body.clone(),
+                url: release.html_url.clone(),
+                checked_at: now,
+                staged: String::new(),
+            };
+            let staged = Path::new(&stage_dir).join(format!("clashtui-{}", latest.tag_name));
+            if cached.tag_name == latest.tag_name && Path::new(&cached.staged).is_file() {
+                latest.staged = cached.staged;
+            } else if !stage_dir.is_empty()
+                && is_newer(&latest.tag_name, crate::VERSION) == Some(true)
+            {
+                let asset = release
+                    .assets
+                    .iter()
+                    .find(|a| a.name.starts_with(&asset_prefix()) && a.name.ends_with(".gz"));
+                match asset {
+                    Some(asset) => match std::fs::create_dir_all(&stage_dir)
+                        .and_then(|_| api.download_gz(&release, &asset.name, with_proxy, &staged))
+                    {
+                        Ok(()) => latest.staged = staged.to_string_lossy().to_string(),
+                        Err(e) => log::warn!("Staging {} => {e}", asset.name),
+                    },
+                    None => log::warn!("No clashtui build for this machine in {}", latest.tag_name),
+                }
+            }
+            let _ = tx.send(Ok(latest));
+        });
+        Some(rx)
+    }
+
+    /// Cache the result of [check_release][Self::check_release]
+    pub fn release_checked(&self, release: LatestRelease) {
+        self.clashtui_data.borrow_mut().latest_release = release;
+        self.save_to_data_file();
+    }
+}
+
+/// e.g. `clashtui-linux-amd64-`, the name of assets is like `clashtui-linux-amd64-v0.2.1.gz`
+fn asset_prefix() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    format!("clashtui-{}-{arch}-", std::env::consts::OS)
+}
+
+/// Whether `latest` is ahead of `current`, `None` if either isn't like `v0.2.1`
+///
+/// `current` might come from `git describe`, e.g. `v0.2.1-8-gd6e96fb-debug`
+pub fn is_newer(latest: &str, current: &str) -> Option<bool> {
+    Some(parse_version(latest)? > parse_version(current)?)
+}
+fn parse_version(v: &str) -> Option<(u64, u64, u64)> {
+    let v = v.trim_start_matches('v');
+    let end = v
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(v.len());
+    let mut nums = v[..end].split('.').map(|n| n.parse::<u64>().ok());
+    Some((
+        nums.next()??,
+        nums.next()??,
+        nums.next().flatten().unwrap_or(0),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn release_check_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("release", &mock);
+        let release = r#"{
+            "id": 1, "name": "v999.0.0", "tag_name": "v999.0.0", "body": "- Something new",
+            "draft": false, "prerelease": false, "published_at": "2024-05-01T00:00:00Z",
+            "assets": []
+        }"#;
+        mock.serve("/clashtui/latest", 200, &[], release);
+        let cfg_path = dir.0.join("config.yaml");
+        let mut cfg = std::fs::read_to_string(&cfg_path).unwrap();
+        cfg.push_str(&format!("release_url: {}/clashtui/latest\n", mock.url()));
+        std::fs::write(&cfg_path, cfg).unwrap();
+
+        let util = dir.util();
+        let latest = util.check_release().unwrap().recv().unwrap().unwrap();
+        assert_eq!(latest.tag_name, "v999.0.0");
+        assert_eq!(latest.changelog, "- Something new");
+        assert!(latest.staged.is_empty());
+        util.release_checked(latest);
+        // cached for a day
+        assert!(dir.util().check_release().is_none());
+        assert_eq!(dir.util().latest_release().tag_name, "v999.0.0");
+    }
+    #[test]
+    fn is_newer_test() {
+        use super::is_newer;
+        assert_eq!(is_newer("v0.2.2", "v0.2.1-8-gd6e96fb-debug"), Some(true));
+        assert_eq!(is_newer("v0.2.1", "v0.2.1-8-gd6e96fb"), Some(false));
+        assert_eq!(is_newer("v0.10", "v0.9.3"), Some(true));
+        assert_eq!(is_newer("v0.3.0", "d6e96fb"), None);
+    }
+}
@@ -0,0 +1,13 @@
+use super::ClashTuiUtil;
+use api::ClashRules;
+use std::sync::mpsc::Sender;
+
+impl ClashTuiUtil {
+    /// Rules loaded by the running core, fetched in background and sent to `tx`
+    pub fn fetch_rules(&self, tx: Sender<Result<ClashRules, String>>) {
+        let api = self.clash_api.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(api.rules().map_err(|e| e.to_string()));
+        });
+    }
+}
@@ -0,0 +1,92 @@
+use super::ClashTuiUtil;
+use api::{ClashUtil, JsonStream, Memory, Traffic};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender},
+    Arc,
+};
+use std::time::Duration;
+
+// The core might be restarted or not running yet
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Clone)]
+pub enum CoreStat {
+    Traffic(Traffic),
+    Memory(Memory),
+    /// The stream is broken, values received before are outdated
+    Disconnected,
+}
+
+impl ClashTuiUtil {
+    /// Follow `/traffic` and `/memory` in background until the receiver is dropped
+    pub fn monitor_core(&self) -> Receiver<CoreStat> {
+        let (tx, rx) = mpsc::channel();
+        // both send every second, a failed send is soon enough
+        let stop = Arc::new(AtomicBool::new(false));
+        follow(
+            self.clash_api.clone(),
+            tx.clone(),
+            stop.clone(),
+            ClashUtil::traffic,
+            CoreStat::Traffic,
+            CoreStat::Disconnected,
+        );
+        follow(
+            self.clash_api.clone(),
+            tx,
+            stop,
+            ClashUtil::memory,
+            CoreStat::Memory,
+            CoreStat::Disconnected,
+        );
+        rx
+    }
+}
+
+/// Read a streaming endpoint in background and reconnect when it breaks, `lost` is
+/// sent every time it breaks. Stop once `tx` is disconnected or `stop` is set,
+/// which is checked even if nothing comes
+pub(super) fn follow<T, M, F, W>(
+    api: ClashUtil,
+    tx: Sender<M>,
+    stop: Arc<AtomicBool>,
+    open: F,
+    wrap: W,
+    lost: M,
+) where
+    T: serde::de::DeserializeOwned + 'static,
+    M: Clone + Send + 'static,
+    F: Fn(&ClashUtil) -> api::Result<JsonStream<T>> + Send + 'static,
+    W: Fn(T) -> M + Send + 'static,
+{
+    std::thread::spawn(move || loop {
+        match open(&api) {
+            Ok(stream) => {
+                for msg in stream {
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    match msg {
+                        Ok(msg) => {
+                            if tx.send(wrap(msg)).is_err() {
+                                return;
+                            }
+                        }
+                        // just quiet
+                        Err(e) if e.is_timeout() => continue,
+                        Err(e) => {
+                            log::debug!("Core stream => {e}");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => log::debug!("Open core stream => {e}"),
+        }
+        if stop.load(Ordering::Relaxed) || tx.send(lost.clone()).is_err() {
+            return;
+        }
+        std::thread::sleep(RECONNECT_INTERVAL);
+    });
+}
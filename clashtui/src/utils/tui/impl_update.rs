@@ -0,0 +1,396 @@
+use super::impl_profile::{
+    download_profile, extract_net_providers, str_subscription, subscription_warning,
+    ProfileHeaders,
+};
+use super::{ClashTuiUtil, ProfileType};
+use api::{ClashUtil, ProfileSectionType};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+};
+
+// Providers downloaded at once if `update_concurrency` is 0
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// State of one download of a [ProfileUpdate]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateState {
+    Queued,
+    Downloading,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+impl std::fmt::Display for UpdateState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Queued => write!(f, "queued"),
+            Self::Downloading => write!(f, "downloading"),
+            Self::Done => write!(f, "done"),
+            Self::Failed(e) => write!(f, "failed, {e}"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// What the worker tells about an update
+enum UpdateEvent {
+    /// More downloads, appended to the known ones
+    Queued(Vec<String>),
+    /// The index of a download and its new state
+    Progress(usize, UpdateState),
+    Finished(std::io::Result<UpdateOutcome>),
+}
+
+/// Result of the worker, recorded by [finish_update][ClashTuiUtil::finish_update]
+pub struct UpdateOutcome {
+    report: Vec<String>,
+    headers: ProfileHeaders,
+    cancelled: bool,
+    // some providers failed to download
+    failed: bool,
+}
+
+/// An update of a profile running in background
+///
+/// Call [poll][Self::poll] until it's finished, then pass the outcome to
+/// [finish_update][ClashTuiUtil::finish_update].
+pub struct ProfileUpdate {
+    pub profile_name: String,
+    /// Each download and its state, the profile itself comes first if it's a subscription
+    pub items: Vec<(String, UpdateState)>,
+    rx: Receiver<UpdateEvent>,
+    cancel: Arc<AtomicBool>,
+}
+impl ProfileUpdate {
+    /// Stop after the running downloads, the rest are cancelled
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+    /// Apply what the worker sent so far, `Some` once finished
+    pub fn poll(&mut self) -> Option<std::io::Result<UpdateOutcome>> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(UpdateEvent::Queued(names)) => self
+                    .items
+                    .extend(names.into_iter().map(|n| (n, UpdateState::Queued))),
+                Ok(UpdateEvent::Progress(idx, state)) => {
+                    if let Some(item) = self.items.get_mut(idx) {
+                        item.1 = state;
+                    }
+                }
+                Ok(UpdateEvent::Finished(outcome)) => return Some(outcome),
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    return Some(Err(std::io::Error::other("The update stopped unexpectedly")))
+                }
+            }
+        }
+    }
+    /// One line per download, e.g. `provider: downloading`
+    pub fn progress(&self) -> Vec<String> {
+        self.items
+            .iter()
+            .map(|(name, state)| format!("{name}: {state}"))
+            .collect()
+    }
+}
+
+/// Everything the worker needs, since [ClashTuiUtil] stays in the UI thread
+struct Updater {
+    clash_api: ClashUtil,
+    clash_cfg_dir: PathBuf,
+    profile_name: String,
+    // (url, cache) of a subscription
+    subscription: Option<(String, PathBuf)>,
+    profile_yaml_path: PathBuf,
+    section_types: Vec<ProfileSectionType>,
+    concurrency: usize,
+}
+impl Updater {
+    fn run(self, tx: &Sender<UpdateEvent>, cancel: &AtomicBool) -> std::io::Result<UpdateOutcome> {
+        let send = |ev| {
+            let _ = tx.send(ev);
+        };
+        let with_proxy = self.clash_api.version().is_ok();
+        let mut report = Vec::new();
+        let mut idx = 0;
+        // from the profile, or else the first proxy-provider sending them
+        let mut headers = ProfileHeaders::default();
+        if let Some((url, cache)) = self.subscription.as_ref() {
+            send(UpdateEvent::Queued(vec![self.profile_name.clone()]));
+            send(UpdateEvent::Progress(idx, UpdateState::Downloading));
+            match download_profile(&self.clash_api, with_proxy, url, cache, ProfileSectionType::Profile) {
+                Ok(h) => {
+                    send(UpdateEvent::Progress(idx, UpdateState::Done));
+                    headers = h;
+                }
+                Err(e) => {
+                    send(UpdateEvent::Progress(idx, UpdateState::Failed(e.to_string())));
+                    return Err(e);
+                }
+            }
+            idx += 1;
+            report.push(format!("Updated: {}, {}", self.profile_name, url));
+        }
+
+        let providers: Vec<(ProfileSectionType, (String, String, String))> =
+            extract_net_providers(&self.profile_yaml_path, &self.section_types)
+                .map(|m| {
+                    m.into_iter()
+                        .flat_map(|(kind, v)| v.into_iter().map(move |p| (kind, p)))
+                        .collect()
+                })
+                .unwrap_or_default();
+        send(UpdateEvent::Queued(
+            providers.iter().map(|(_, (name, _, _))| name.clone()).collect(),
+        ));
+
+        // Each worker takes the next provider until none is left
+        let next = AtomicUsize::new(0);
+        let download = || {
+            let mut done = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some((kind, (_, url, path))) = providers.get(i) else {
+                    break;
+                };
+                if cancel.load(Ordering::Relaxed) {
+                    send(UpdateEvent::Progress(idx + i, UpdateState::Cancelled));
+                    done.push((i, None));
+                    continue;
+                }
+                send(UpdateEvent::Progress(idx + i, UpdateState::Downloading));
+                let r = download_profile(
+                    &self.clash_api,
+                    with_proxy,
+                    url,
+                    &self.clash_cfg_dir.join(path),
+                    *kind,
+                );
+                send(UpdateEvent::Progress(
+                    idx + i,
+                    match &r {
+                        Ok(_) => UpdateState::Done,
+                        Err(e) => UpdateState::Failed(e.to_string()),
+                    },
+                ));
+                done.push((i, Some(r)));
+            }
+            done
+        };
+        let workers = self.concurrency.clamp(1, providers.len().max(1));
+        let mut done: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..workers).map(|_| s.spawn(download)).collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+        // reported in the order of the profile
+        done.sort_by_key(|(i, _)| *i);
+
+        let (mut cancelled, mut failed) = (false, false);
+        for (i, r) in done {
+            let (_, (name, url, _)) = &providers[i];
+            match r {
+                Some(Ok(h)) => {
+                    headers = headers.or(h);
+                    report.push(format!("Updated: {}, {}", name, url))
+                }
+                Some(Err(e)) => {
+                    failed = true;
+                    report.push(format!("Not updated: {}, {}, {}", name, url, e))
+                }
+                None => {
+                    cancelled = true;
+                    report.push(format!("Not updated: {}, {}, Cancelled", name, url));
+                }
+            }
+        }
+
+        Ok(UpdateOutcome {
+            report,
+            headers,
+            cancelled,
+            failed,
+        })
+    }
+}
+
+impl ClashTuiUtil {
+    fn updater(&self, profile_name: &str, does_update_all: bool) -> std::io::Result<Updater> {
+        let mut profile_yaml_path = self.profile_dir.join(profile_name);
+        let mut subscription = None;
+        if self.get_profile_type(profile_name) == Some(ProfileType::Url) {
+            let sub_url = self.extract_profile_url(profile_name)?;
+            profile_yaml_path = self.get_profile_cache_unchecked(profile_name);
+            // Update the file to keep up-to-date
+            subscription = Some((sub_url, profile_yaml_path.clone()));
+        }
+
+        let mut section_types = vec![ProfileSectionType::ProxyProvider];
+        if does_update_all {
+            section_types.push(ProfileSectionType::RuleProvider);
+        }
+        Ok(Updater {
+            clash_api: self.clash_api.clone(),
+            clash_cfg_dir: PathBuf::from(&self.tui_cfg.clash_cfg_dir),
+            profile_name: profile_name.to_string(),
+            subscription,
+            profile_yaml_path,
+            section_types,
+            concurrency: match self.tui_cfg.update_concurrency {
+                0 => DEFAULT_CONCURRENCY,
+                n => n,
+            },
+        })
+    }
+
+    /// Update `profile_name` and its proxy-providers in background, rule-providers as well if `does_update_all`
+    pub fn start_update(&self, profile_name: &str, does_update_all: bool) -> std::io::Result<ProfileUpdate> {
+        let updater = self.updater(profile_name, does_update_all)?;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let flag = cancel.clone();
+        std::thread::spawn(move || {
+            let outcome = updater.run(&tx, &flag);
+            let _ = tx.send(UpdateEvent::Finished(outcome));
+        });
+        Ok(ProfileUpdate {
+            profile_name: profile_name.to_string(),
+            items: Vec::new(),
+            rx,
+            cancel,
+        })
+    }
+
+    /// Record the outcome of [start_update][Self::start_update], the report is returned
+    ///
+    /// Only an update with every download done counts as the last update of `profile_name`,
+    /// so a cancelled or partly failed one is retried
+    pub fn finish_update(&self, profile_name: &str, outcome: UpdateOutcome) -> Vec<String> {
+        let UpdateOutcome {
+            mut report,
+            headers,
+            cancelled,
+            failed,
+        } = outcome;
+        let now = chrono::Utc::now().timestamp();
+        if let Some(info) = headers.subscription.as_ref() {
+            report.push(format!("Subscription: {}", str_subscription(info, now)));
+            report.extend(subscription_warning(profile_name, info, now));
+        }
+        {
+            let mut data = self.clashtui_data.borrow_mut();
+            let profile = data.profiles.entry(profile_name.to_string()).or_default();
+            if headers.subscription.is_some() || !cancelled {
+                profile.subscription = headers.subscription;
+            }
+            if cancelled {
+                report.push("Cancelled".to_string());
+            } else if !failed {
+                profile.update_interval = headers.update_interval;
+                profile.last_update = now;
+            }
+        }
+        self.save_to_data_file();
+        report
+    }
+
+    pub fn update_profile(
+        &self,
+        profile_name: &str,
+        does_update_all: bool,
+    ) -> std::io::Result<Vec<String>> {
+        self.update_profile_with_clashtui(profile_name, does_update_all)
+    }
+
+    // The advantage of using this interface for updates is that you can know the reason for update failures without needing to check mihomo's logs. The downside is that it requires resolving file permission issues.
+    pub fn update_profile_with_clashtui(
+        &self,
+        profile_name: &str,
+        does_update_all: bool,
+    ) -> std::io::Result<Vec<String>> {
+        // no one is watching the progress
+        let (tx, _) = mpsc::channel();
+        let outcome = self
+            .updater(profile_name, does_update_all)?
+            .run(&tx, &AtomicBool::new(false))?;
+        Ok(self.finish_update(profile_name, outcome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_dir::TestDir;
+    use api::mock::MockController;
+
+    #[test]
+    fn background_update_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("background_update", &mock);
+        let profile = format!(
+            "proxy-providers:\n  a:\n    url: {0}/a\n    path: ./a.yaml\n  b:\n    url: {0}/b\n    path: ./b.yaml\n",
+            mock.url()
+        );
+        mock.serve("/sub", 200, &[], profile);
+        mock.serve("/a", 200, &[], "proxies: []\n");
+        mock.serve("/b", 200, &[], "proxies: []\n");
+        std::fs::write(dir.0.join("profiles/sub"), format!("{}/sub\n", mock.url())).unwrap();
+        let util = dir.util();
+        let wait = |update: &mut super::ProfileUpdate| loop {
+            if let Some(outcome) = update.poll() {
+                return outcome.unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        let mut update = util.start_update("sub", false).unwrap();
+        let outcome = wait(&mut update);
+        assert_eq!(update.progress(), ["sub: done", "a: done", "b: done"]);
+        let report = util.finish_update("sub", outcome);
+        assert_eq!(report.len(), 3);
+        assert!(dir.0.join("b.yaml").is_file());
+        assert!(!util.is_profile_due("sub", chrono::Utc::now().timestamp()));
+
+        // the profile is downloaded before the flag is seen
+        std::fs::remove_file(dir.0.join("b.yaml")).unwrap();
+        let mut update = util.start_update("sub", false).unwrap();
+        update.cancel();
+        let outcome = wait(&mut update);
+        assert_eq!(update.progress(), ["sub: done", "a: cancelled", "b: cancelled"]);
+        assert_eq!(util.finish_update("sub", outcome).last().unwrap(), "Cancelled");
+        assert!(!dir.0.join("b.yaml").exists());
+    }
+    #[test]
+    fn parallel_update_test() {
+        let mock = MockController::start("test");
+        let dir = TestDir::new("parallel_update", &mock);
+        let mut profile = "proxy-providers:\n".to_string();
+        for p in 0..6 {
+            profile += &format!("  p{p}:\n    url: {0}/p{p}\n    path: ./p{p}.yaml\n", mock.url());
+            mock.serve(&format!("/p{p}"), 200, &[], "proxies: []\n");
+        }
+        std::fs::write(dir.0.join("profiles/local.yaml"), profile).unwrap();
+        mock.delay_files(std::time::Duration::from_millis(100));
+
+        let mut util = dir.util();
+        util.tui_cfg.update_concurrency = 3;
+        let report = util.update_profile("local.yaml", false).unwrap();
+        assert_eq!(mock.max_files_in_flight(), 3);
+        // in the order of the profile
+        let expected: Vec<String> = (0..6)
+            .map(|p| format!("Updated: p{p}, {}/p{p}", mock.url()))
+            .collect();
+        assert_eq!(report, expected);
+        assert!(dir.0.join("p5.yaml").is_file());
+    }
+}
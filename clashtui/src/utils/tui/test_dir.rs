@@ -0,0 +1,50 @@
+//! A clashtui dir for the tests of [ClashTuiUtil], using the mock as the core
+use super::ClashTuiUtil;
+use api::mock::MockController;
+use std::path::PathBuf;
+
+/// A clashtui dir using `mock` as the core, removed once dropped
+pub(super) struct TestDir(pub(super) PathBuf);
+impl TestDir {
+    pub(super) fn new(name: &str, mock: &MockController) -> Self {
+        // downloads through the proxy reach the mock as well
+        let port = mock.addr().rsplit(':').next().unwrap().to_string();
+        Self::with_basic(
+            name,
+            &format!("mixed-port: {port}\nexternal-controller: {}", mock.addr()),
+        )
+    }
+    /// `controller` is the controller part of the basic config
+    pub(super) fn with_controller(name: &str, controller: &str) -> Self {
+        Self::with_basic(name, &format!("mixed-port: 7890\n{controller}"))
+    }
+    pub(super) fn with_basic(name: &str, basic: &str) -> Self {
+        let dir = std::env::temp_dir()
+            .join(format!("clashtui_test_{}_{name}", std::process::id()));
+        std::fs::create_dir_all(dir.join("profiles")).unwrap();
+        std::fs::write(
+            dir.join(super::BASIC_FILE),
+            format!("{basic}\nsecret: test\n"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("config.yaml"),
+            format!(
+                "clash_cfg_dir: {0}\nclash_cfg_path: {0}/config.yaml\nclash_core_path: mihomo\n",
+                dir.display()
+            ),
+        )
+        .unwrap();
+        Self(dir)
+    }
+    pub(super) fn util(&self) -> ClashTuiUtil {
+        let (util, errs) = ClashTuiUtil::new(&self.0, true);
+        assert!(errs.is_empty());
+        util
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -30,7 +30,7 @@ pub(super) fn is_yaml(path: &std::path::Path) -> bool {
 
 pub(super) fn parse_yaml(yaml_path: &std::path::Path) -> std::io::Result<serde_yaml::Value> {
     serde_yaml::from_reader(std::fs::File::open(yaml_path)?)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .map_err(std::io::Error::other)
 }
 
 pub fn get_mtime<P>(file_path: P) -> std::io::Result<std::time::SystemTime>
@@ -64,6 +64,21 @@ pub fn str_duration(t: std::time::Duration) -> String {
     }
 }
 
+pub fn str_bytes(b: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = b as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{b}B")
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
 pub fn modify_file_perms_in_dir(dir: &PathBuf, group_name: &str) {
     // dir add set-group-id: `chmod g+s dir`
     if let Ok(metadata) = std::fs::metadata(dir) {
@@ -111,21 +126,19 @@ pub fn find_files_not_group_writable(dir: &PathBuf) -> Vec<PathBuf> {
     let mut result = Vec::new();
 
     if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                let metadata = entry.metadata().unwrap();
-                if metadata.is_file() {
-                    let permissions = metadata.permissions();
-
-                    if permissions.mode() & 0o0020 == 0 {
-                        result.push(path.clone());
-                    }
-                }
-                if metadata.is_dir() {
-                    result.extend(find_files_not_group_writable(&path));
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let metadata = entry.metadata().unwrap();
+            if metadata.is_file() {
+                let permissions = metadata.permissions();
+
+                if permissions.mode() & 0o0020 == 0 {
+                    result.push(path.clone());
                 }
             }
+            if metadata.is_dir() {
+                result.extend(find_files_not_group_writable(&path));
+            }
         }
     }
 
@@ -144,23 +157,21 @@ pub fn find_files_not_in_group(dir: &PathBuf, group_name: &str) -> Vec<PathBuf>
     let mut result = Vec::new();
 
     if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let metadata = entry.metadata().unwrap();
-
-                if metadata.is_file() {
-                    let file_gid = metadata.gid();
-                    if let Ok(Some(group)) =
-                        Group::from_gid(Gid::from_raw(file_gid))
-                    {
-                        if group.name != group_name {
-                            result.push(entry.path().clone());
-                        }
+        for entry in entries.flatten() {
+            let metadata = entry.metadata().unwrap();
+
+            if metadata.is_file() {
+                let file_gid = metadata.gid();
+                if let Ok(Some(group)) =
+                    Group::from_gid(Gid::from_raw(file_gid))
+                {
+                    if group.name != group_name {
+                        result.push(entry.path().clone());
                     }
-                } else if metadata.is_dir() {
-                    let sub_dir = entry.path();
-                    result.extend(find_files_not_in_group(&sub_dir, group_name));
                 }
+            } else if metadata.is_dir() {
+                let sub_dir = entry.path();
+                result.extend(find_files_not_in_group(&sub_dir, group_name));
             }
         }
     }
@@ -216,7 +227,34 @@ pub fn mock_fileop_as_sudo_user() {
 }
 
 pub fn is_run_as_root() -> bool {
-    return geteuid().is_root();
+    geteuid().is_root()
+}
+
+/// Whether the running process named `name` may create a TUN device,
+/// `None` if it's not found
+pub fn proc_has_net_admin(name: &str) -> Option<bool> {
+    // `comm` is cut to 15 bytes
+    let comm = name.get(..15).unwrap_or(name);
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|s| s.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .find(|e| {
+            std::fs::read_to_string(e.path().join("comm"))
+                .is_ok_and(|s| s.trim_end() == comm)
+        })
+        .and_then(|e| std::fs::read_to_string(e.path().join("status")).ok())
+        .and_then(|status| has_net_admin(&status))
+}
+fn has_net_admin(status: &str) -> Option<bool> {
+    const CAP_NET_ADMIN: u32 = 12;
+    let cap_eff = status.lines().find_map(|l| l.strip_prefix("CapEff:"))?;
+    let cap_eff = u64::from_str_radix(cap_eff.trim(), 16).ok()?;
+    Some(cap_eff & (1 << CAP_NET_ADMIN) != 0)
 }
 
 pub fn restore_fileop_as_root() {
@@ -12,6 +12,6 @@ impl std::error::Error for Infailable {}
 
 impl From<Infailable> for std::io::Error {
     fn from(_: Infailable) -> Self {
-        std::io::Error::new(std::io::ErrorKind::Other, "Should Not fail")
+        std::io::Error::other("Should Not fail")
     }
 }
@@ -26,8 +26,30 @@ pub struct Theme {
     pub tabbar_hl_fg: Color,
 
     pub statusbar_text_fg: Color,
+    pub statusbar_traffic_up_fg: Color,
+    pub statusbar_traffic_down_fg: Color,
+    pub statusbar_memory_fg: Color,
 
+    pub profile_info_fg: Color,
     pub profile_update_interval_fg: Color,
+
+    pub proxy_delay_low_fg: Color,
+    pub proxy_delay_medium_fg: Color,
+    pub proxy_delay_high_fg: Color,
+    pub proxy_delay_unknown_fg: Color,
+
+    pub connection_info_fg: Color,
+
+    pub rule_info_fg: Color,
+
+    pub provider_info_fg: Color,
+    pub provider_ok_fg: Color,
+    pub provider_failed_fg: Color,
+
+    pub log_debug_fg: Color,
+    pub log_info_fg: Color,
+    pub log_warning_fg: Color,
+    pub log_error_fg: Color,
 }
 
 impl Theme {
@@ -67,8 +89,30 @@ impl Default for Theme {
             tabbar_hl_fg: Color::Rgb(46, 204, 113),
 
             statusbar_text_fg: Color::Rgb(20, 122, 122),
+            statusbar_traffic_up_fg: Color::Rgb(230, 126, 34),
+            statusbar_traffic_down_fg: Color::Rgb(52, 152, 219),
+            statusbar_memory_fg: Color::Rgb(155, 89, 182),
 
+            profile_info_fg: Color::Rgb(192, 192, 192),
             profile_update_interval_fg: Color::Red,
+
+            proxy_delay_low_fg: Color::Green,
+            proxy_delay_medium_fg: Color::Yellow,
+            proxy_delay_high_fg: Color::Red,
+            proxy_delay_unknown_fg: Color::Rgb(192, 192, 192),
+
+            connection_info_fg: Color::Rgb(192, 192, 192),
+
+            rule_info_fg: Color::Rgb(192, 192, 192),
+
+            provider_info_fg: Color::Rgb(192, 192, 192),
+            provider_ok_fg: Color::Green,
+            provider_failed_fg: Color::Red,
+
+            log_debug_fg: Color::Rgb(128, 128, 128),
+            log_info_fg: Color::Reset,
+            log_warning_fg: Color::Yellow,
+            log_error_fg: Color::Red,
         }
     }
 }
@@ -79,6 +79,8 @@ impl InputPopup {
 
     pub fn set_pre_data(&mut self, info: String) {
         self.input = info;
+        // keep typing after the pre-filled text
+        self.cursor_position = self.input.len();
     }
 
     pub fn handle_enter_ev(&mut self) {
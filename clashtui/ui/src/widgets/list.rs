@@ -11,7 +11,7 @@ pub struct List {
     title: String,
     is_visible: bool,
     items: Vec<String>,
-    extra: Option<Vec<String>>,
+    extra: Option<Vec<(String, Ra::Color)>>,
     list_state: Raw::ListState,
     scrollbar: Raw::ScrollbarState,
 }
@@ -54,13 +54,13 @@ impl List {
 
         f.render_stateful_widget(
             if let Some(vc) = self.extra.as_ref() {
-                Raw::List::from_iter(self.items.iter().zip(vc.iter()).map(|(v, e)| {
+                Raw::List::from_iter(self.items.iter().zip(vc.iter()).map(|(v, (e, fg))| {
                     Raw::ListItem::new(
                         Ra::Line::from(vec![
                              Ra::Span::styled(v.to_owned(), Ra::Style::default()),
                              Ra::Span::styled(" ".to_owned(), Ra::Style::default()),
                              //Ra::Span::styled(e, Ra::Style::default().fg(Ra::Color::Rgb(192, 192, 192)))
-                             Ra::Span::styled(e, Ra::Style::default().fg(Theme::get().profile_update_interval_fg))
+                             Ra::Span::styled(e, Ra::Style::default().fg(*fg))
                         ])
                     )
                 }))
@@ -112,6 +112,13 @@ impl List {
         self.list_state.selected().map(|i| &self.items[i])
     }
 
+    /// Useful when items are not unique
+    pub fn selected_index(&self) -> Option<usize> {
+        self.list_state
+            .selected()
+            .filter(|i| *i < self.items.len())
+    }
+
     fn next(&mut self) {
         if self.items.is_empty() {
             return;
@@ -164,7 +171,7 @@ impl List {
                 if i == 0 {
                     self.list_state.select(None);
                 } else if i >= items.len() {
-                    self.list_state.select(Some(items.len() - 1));
+                    self.list_state.select(items.len().checked_sub(1));
                 }
             }
             None => self.list_state.select(None),
@@ -181,6 +188,15 @@ impl List {
     pub fn set_extras<I>(&mut self, extra: I)
     where
         I: Iterator<Item = String> + ExactSizeIterator,
+    {
+        let fg = Theme::get().profile_update_interval_fg;
+        self.set_colored_extras(extra.map(|e| (e, fg)));
+    }
+
+    /// Same as [set_extras][List::set_extras], but each extra has its own color
+    pub fn set_colored_extras<I>(&mut self, extra: I)
+    where
+        I: Iterator<Item = (String, Ra::Color)> + ExactSizeIterator,
     {
         assert_eq!(self.items.len(), extra.len());
         self.extra.replace(Vec::from_iter(extra));
@@ -195,4 +211,10 @@ impl List {
             self.list_state.select(Some(index));
         }
     }
+
+    pub fn select_index(&mut self, index: usize) {
+        if index < self.items.len() {
+            self.list_state.select(Some(index));
+        }
+    }
 }